@@ -0,0 +1,34 @@
+//! Times node creation and `backward` on deep chains and wide sums of increasing size.
+//!
+//! Run with `cargo bench --bench backward`.
+
+use micrograd::bench::{chain, time_backward, wide_sum};
+use micrograd::value::Value;
+use std::time::Instant;
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+fn main() {
+    for n in SIZES {
+        let start = Instant::now();
+        let values: Vec<Value> = (0..n).map(|i| Value::new(i as f64)).collect();
+        let elapsed = start.elapsed();
+        drop(values);
+        println!(
+            "{:<10} n = {:>6}  {:>10.3} ms",
+            "new",
+            n,
+            elapsed.as_secs_f64() * 1e3
+        );
+    }
+    for (name, build) in [("chain", chain as fn(usize) -> _), ("wide_sum", wide_sum)] {
+        for (n, elapsed) in time_backward(build, &SIZES) {
+            println!(
+                "{:<10} n = {:>6}  {:>10.3} ms",
+                name,
+                n,
+                elapsed.as_secs_f64() * 1e3
+            );
+        }
+    }
+}
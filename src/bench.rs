@@ -0,0 +1,62 @@
+use crate::value::Value;
+use std::time::{Duration, Instant};
+
+/// x * w_1 * w_2 * ... * w_n: a graph `n` nodes deep, which stresses the topological sort.
+pub fn chain(n: usize) -> Value {
+    (0..n).fold(Value::new(1.0), |acc, i| {
+        acc * Value::new(1.0 + 1e-3 * (i % 7) as f64)
+    })
+}
+
+/// x_1 * w_1 + ... + x_n * w_n: `n` products with two leaves each, added up pairwise in
+/// a balanced tree. The graph is only about log2(n) nodes deep, so unlike `chain` it
+/// stresses the dispatch of many backward closures rather than the topological sort.
+pub fn wide_sum(n: usize) -> Value {
+    let mut level: Vec<Value> = (0..n)
+        .map(|i| Value::new(i as f64 * 1e-3) * Value::new(0.5))
+        .collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| pair.iter().cloned().reduce(|a, b| a + b).unwrap())
+            .collect();
+    }
+    level.pop().unwrap_or_default()
+}
+
+/// Builds a graph of every size in `sizes` with `build` and times one `backward` on it.
+pub fn time_backward(build: impl Fn(usize) -> Value, sizes: &[usize]) -> Vec<(usize, Duration)> {
+    sizes
+        .iter()
+        .map(|&n| {
+            let root = build(n);
+            let start = Instant::now();
+            root.backward();
+            (n, start.elapsed())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn smoke() {
+        for build in [chain, wide_sum] {
+            let timings = time_backward(build, &[1, 10, 100]);
+            assert_eq!(
+                timings.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+                vec![1, 10, 100]
+            );
+        }
+        let root = chain(50);
+        root.backward();
+        assert!(root.grad() == 1.0 && root.data().is_finite());
+
+        // 0.5e-3 * (0 + 1 + ... + 99)
+        assert_approx_eq!(wide_sum(100).data(), 0.5e-3 * 4950.0);
+        assert_eq!(wide_sum(0).data(), 0.0);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::train::EpochMetric;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::fs::File;
+use std::io::{BufWriter, Result, Write};
+
+/// Writes the training history as CSV with an `epoch,loss,lr,grad_norm` header.
+pub fn write_metrics_csv(path: &str, history: &[EpochMetric]) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "epoch,loss,lr,grad_norm")?;
+    for m in history {
+        writeln!(out, "{},{},{},{}", m.epoch, m.loss, m.lr, m.grad_norm)?;
+    }
+    out.flush()
+}
+
+/// A permutation of `0..n` that depends only on `seed` and `epoch`, so every epoch
+/// visits the samples in a different but reproducible order. `Mlp::train` uses it for
+/// `TrainConfig::with_shuffle`.
+pub fn shuffle_indices(n: usize, seed: u64, epoch: usize) -> Vec<usize> {
+    // mix the epoch in with the golden-ratio constant so nearby (seed, epoch) pairs
+    // still give unrelated streams
+    let mut rng = StdRng::seed_from_u64(seed ^ (epoch as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    let mut indices: Vec<usize> = (0..n).collect();
+    indices.shuffle(&mut rng);
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mlp::Mlp;
+    use crate::train::TrainConfig;
+    use std::fs::{read_to_string, remove_file};
+
+    #[test]
+    fn metrics_csv() {
+        let mlp = Mlp::new(2, vec![3, 1], true);
+        let history = mlp.train(
+            vec![vec![0.0, 1.0], vec![1.0, 0.0]],
+            vec![1.0, -1.0],
+            &TrainConfig::new(5, 0.1),
+        );
+        let path = std::env::temp_dir().join("micrograd_metrics_csv_test.csv");
+        let path = path.to_str().unwrap();
+
+        write_metrics_csv(path, &history).unwrap();
+        let csv = read_to_string(path).unwrap();
+        remove_file(path).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "epoch,loss,lr,grad_norm");
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].starts_with("0,"));
+        assert_eq!(lines[5].split(',').count(), 4);
+    }
+
+    #[test]
+    fn shuffle() {
+        let order = shuffle_indices(20, 7, 0);
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+
+        assert_eq!(shuffle_indices(20, 7, 0), order);
+        assert_ne!(shuffle_indices(20, 7, 1), order);
+        assert_ne!(shuffle_indices(20, 8, 0), order);
+        assert!(shuffle_indices(0, 7, 0).is_empty());
+    }
+}
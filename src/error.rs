@@ -0,0 +1,53 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The data does not fit the shape of the network.
+    Shape(String),
+    /// A serialized graph is malformed or cannot be rebuilt.
+    Graph(String),
+    /// An activation exceeded the limit set with `Mlp::with_activation_limit`.
+    Activation {
+        layer: usize,
+        value: f64,
+        limit: f64,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "io error: {}", e),
+            Error::Json(e) => write!(f, "json error: {}", e),
+            Error::Shape(msg) => write!(f, "shape mismatch: {}", msg),
+            Error::Graph(msg) => write!(f, "invalid graph: {}", msg),
+            Error::Activation {
+                layer,
+                value,
+                limit,
+            } => write!(
+                f,
+                "activation {} in layer {} exceeds the limit of {}",
+                value, layer, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
@@ -0,0 +1,282 @@
+use crate::value::{fast_tanh, sigmoid, EXP_CLAMP};
+use crate::DEFAULT_EPS;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// Dual number `value + grad * ε` with ε² = 0, for forward-mode differentiation.
+///
+/// Seeding the inputs with the components of a direction (`Dual::variable` for a
+/// single input) and evaluating a function on duals yields the function value and its
+/// derivative along that direction in one pass, without building a graph. This is the
+/// forward-mode counterpart of `Value`, cheaper when there are few inputs and many
+/// outputs.
+///
+/// Every op clamps its input the same way as the `Value` method of the same name, so
+/// both engines agree everywhere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Dual {
+    pub value: f64,
+    pub grad: f64,
+}
+
+impl Dual {
+    pub fn new(value: f64, grad: f64) -> Self {
+        Self { value, grad }
+    }
+
+    /// The input being differentiated with respect to: d(x)/d(x) = 1.
+    pub fn variable(value: f64) -> Self {
+        Self::new(value, 1.0)
+    }
+
+    /// (a + bε)^n = a^n + n * a^(n - 1) * bε
+    pub fn pow(self, n: f64) -> Self {
+        Self::new(self.value.powf(n), n * self.value.powf(n - 1.0) * self.grad)
+    }
+
+    /// tanh(a + bε) = tanh(a) + (1 - tanh(a)^2) * bε
+    pub fn tanh(self) -> Self {
+        let t = self.value.tanh();
+        Self::new(t, (1.0 - t * t) * self.grad)
+    }
+
+    /// Like `tanh`, with the approximation `value::fast_tanh`.
+    pub fn tanh_fast(self) -> Self {
+        let t = fast_tanh(self.value);
+        Self::new(t, (1.0 - t * t) * self.grad)
+    }
+
+    /// sigmoid(a + bε) = s + s * (1 - s) * bε with s = sigmoid(a)
+    pub fn sigmoid(self) -> Self {
+        let s = sigmoid(self.value);
+        Self::new(s, s * (1.0 - s) * self.grad)
+    }
+
+    /// e^(a + bε) = e^a + e^a * bε, with a clamped to [-EXP_CLAMP, EXP_CLAMP].
+    pub fn exp(self) -> Self {
+        let e = self.value.clamp(-EXP_CLAMP, EXP_CLAMP).exp();
+        Self::new(e, e * self.grad)
+    }
+
+    /// ln(a + bε) = ln(a) + bε / a, with a clamped to at least `DEFAULT_EPS`.
+    pub fn ln(self) -> Self {
+        self.ln_eps(DEFAULT_EPS)
+    }
+
+    /// `ln` with the input clamped to at least `eps` instead.
+    pub fn ln_eps(self, eps: f64) -> Self {
+        let a = self.value.max(eps);
+        Self::new(a.ln(), self.grad / a)
+    }
+
+    /// sqrt(a + bε) = sqrt(a) + bε / (2 * sqrt(a)), with a clamped to at least
+    /// `DEFAULT_EPS`.
+    pub fn sqrt(self) -> Self {
+        self.sqrt_eps(DEFAULT_EPS)
+    }
+
+    /// `sqrt` with the input clamped to at least `eps` instead.
+    pub fn sqrt_eps(self, eps: f64) -> Self {
+        let r = self.value.max(eps).sqrt();
+        Self::new(r, 0.5 / r * self.grad)
+    }
+
+    /// max(0, a), with derivative b for a positive a and 0 otherwise.
+    pub fn relu(self) -> Self {
+        if self.value > 0.0 {
+            self
+        } else {
+            Self::new(0.0, 0.0)
+        }
+    }
+
+    /// a for a positive a and alpha * a otherwise, with the matching derivative.
+    pub fn leaky_relu(self, alpha: f64) -> Self {
+        if self.value > 0.0 {
+            self
+        } else {
+            Self::new(alpha * self.value, alpha * self.grad)
+        }
+    }
+
+    /// a clamped to [lo, hi], with derivative b inside the range and 0 outside.
+    pub fn clamp(self, lo: f64, hi: f64) -> Self {
+        if (lo..=hi).contains(&self.value) {
+            self
+        } else {
+            Self::new(self.value.clamp(lo, hi), 0.0)
+        }
+    }
+
+    /// The larger operand, with its derivative.
+    pub fn max(self, other: Self) -> Self {
+        if self.value >= other.value {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl From<f64> for Dual {
+    /// A constant: its derivative is zero.
+    fn from(value: f64) -> Self {
+        Self::new(value, 0.0)
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.value, -self.grad)
+    }
+}
+
+impl Add for Dual {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.value + rhs.value, self.grad + rhs.grad)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.value - rhs.value, self.grad - rhs.grad)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.value * rhs.value,
+            self.value * rhs.grad + self.grad * rhs.value,
+        )
+    }
+}
+
+/// (a + bε) / (c + dε) = a / c + (b * c - a * d) / c^2 * ε, following `f64` semantics
+/// for a zero divisor like `Value`.
+impl Div for Dual {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.value / rhs.value,
+            (self.grad * rhs.value - self.value * rhs.grad) / (rhs.value * rhs.value),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Value;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn chain() {
+        // f = (a + b) * (a * b), differentiated along a and then along b
+        let f = |a: Dual, b: Dual| (a + b) * (a * b);
+        let da = f(Dual::variable(-2.0), Dual::from(3.0));
+        let db = f(Dual::from(-2.0), Dual::variable(3.0));
+
+        let a = Value::new(-2.0);
+        let b = Value::new(3.0);
+        let out = (a.clone() + b.clone()) * (a.clone() * b.clone());
+        out.backward();
+
+        assert_eq!(da.value, out.data());
+        assert_eq!(da.grad, a.grad());
+        assert_eq!(db.grad, b.grad());
+    }
+
+    #[test]
+    fn sub() {
+        let a = Value::new(3.0);
+        let b = Value::new(5.0);
+        (a.clone() - b.clone()).backward();
+
+        assert_eq!((Dual::variable(3.0) - Dual::from(5.0)).grad, a.grad());
+        assert_eq!((Dual::from(3.0) - Dual::variable(5.0)).grad, b.grad());
+    }
+
+    #[test]
+    fn pow() {
+        let a = Value::new(5.0);
+        a.pow(&Value::new(3.0)).backward();
+
+        let out = Dual::variable(5.0).pow(3.0);
+        assert_eq!(out.value, 125.0);
+        assert_eq!(out.grad, a.grad());
+    }
+
+    #[test]
+    fn elementary() {
+        type Pair = (fn(&Value) -> Value, fn(Dual) -> Dual);
+        let ops: [Pair; 11] = [
+            (|v| v.tanh(), |d| d.tanh()),
+            (|v| v.tanh_fast(), |d| d.tanh_fast()),
+            (|v| v.sigmoid(), |d| d.sigmoid()),
+            (|v| v.exp(), |d| d.exp()),
+            (|v| v.ln(), |d| d.ln()),
+            (|v| v.sqrt(), |d| d.sqrt()),
+            (|v| v.relu(), |d| d.relu()),
+            (|v| v.leaky_relu(0.1), |d| d.leaky_relu(0.1)),
+            (|v| v.clamp(-1.0, 1.0), |d| d.clamp(-1.0, 1.0)),
+            (|v| -v.clone(), |d| -d),
+            (|v| v.pow(&Value::new(3.0)), |d| d.pow(3.0)),
+        ];
+        // the extremes exercise the clamping of exp, ln and sqrt
+        for x in [-1000.0, -1.5, -0.2, 0.0, 0.3, 0.8814, 2.0, 1000.0] {
+            for (reverse, forward) in ops {
+                let a = Value::new(x);
+                let out = reverse(&a);
+                out.backward();
+                let dual = forward(Dual::variable(x));
+                assert_eq!(dual.value, out.data(), "value at {}", x);
+                assert_eq!(dual.grad, a.grad(), "grad at {}", x);
+            }
+        }
+
+        let out = Dual::variable(-1.0).max(Dual::from(0.0));
+        assert_eq!((out.value, out.grad), (0.0, 0.0));
+    }
+
+    #[test]
+    fn div() {
+        // f = (a + b) / c
+        let f = |a: Dual, b: Dual, c: Dual| (a + b) / c;
+        let a = Value::new(1.0);
+        let b = Value::new(2.0);
+        let c = Value::new(-4.0);
+        let out = (a.clone() + b.clone()) / c.clone();
+        out.backward();
+
+        let da = f(Dual::variable(1.0), Dual::from(2.0), Dual::from(-4.0));
+        let dc = f(Dual::from(1.0), Dual::from(2.0), Dual::variable(-4.0));
+        assert_eq!(da.value, out.data());
+        assert_eq!(da.grad, a.grad());
+        assert_eq!(dc.grad, c.grad());
+        assert!((Dual::from(1.0) / Dual::from(0.0)).value.is_infinite());
+    }
+
+    #[test]
+    fn directional_derivative() {
+        // f(x, y) = tanh(x * y) + e^x along (1, 2) is df/dx + 2 * df/dy
+        let f = |x: Dual, y: Dual| (x * y).tanh() + x.exp();
+        let out = f(Dual::new(0.4, 1.0), Dual::new(-0.7, 2.0));
+
+        let x = Value::new(0.4);
+        let y = Value::new(-0.7);
+        let reverse = (x.clone() * y.clone()).tanh() + x.exp();
+        reverse.backward();
+
+        assert_approx_eq!(out.value, reverse.data());
+        assert_approx_eq!(out.grad, x.grad() + 2.0 * y.grad());
+    }
+}
@@ -1,41 +1,274 @@
-use crate::neuron::Neuron;
+use crate::neuron::{Neuron, TanhImpl};
 use crate::value::Value;
+use rand::Rng;
 
 #[derive(Clone, Debug)]
 pub struct Layer {
     neurons: Vec<Neuron>,
+    frozen: bool,
 }
 
 impl Layer {
     pub fn new(nin: usize, nout: usize, activation: bool) -> Self {
         let neurons = (0..nout).map(|_| Neuron::new(nin, activation)).collect();
-        Self { neurons }
+        Self::from_neurons(neurons)
     }
 
+    pub fn from_neurons(neurons: Vec<Neuron>) -> Self {
+        Self {
+            neurons,
+            frozen: false,
+        }
+    }
+
+    /// Selects how every neuron of the layer computes tanh.
+    pub fn with_tanh_impl(mut self, tanh: TanhImpl) -> Self {
+        self.neurons = self
+            .neurons
+            .into_iter()
+            .map(|n| n.with_tanh_impl(tanh))
+            .collect();
+        self
+    }
+
+    /// A frozen layer is not trained: `update` leaves its parameters alone, and
+    /// `Mlp::train` discards the gradients `backward` accumulates in them.
+    pub fn with_frozen(mut self, frozen: bool) -> Self {
+        self.frozen = frozen;
+        self
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn neurons(&self) -> &[Neuron] {
+        &self.neurons
+    }
+
+    /// Parameters of each neuron, in neuron order.
     pub fn parameters(&self) -> Vec<Value> {
         self.neurons.iter().flat_map(|n| n.parameters()).collect()
     }
 
+    /// Re-samples the parameters of every neuron in place.
+    pub fn reset(&self, rng: &mut impl Rng) {
+        self.neurons.iter().for_each(|n| n.reset(rng));
+    }
+
+    /// A copy of the layer backed by new parameter nodes.
+    pub fn deep_copy(&self) -> Self {
+        let neurons = self.neurons.iter().map(|n| n.deep_copy()).collect();
+        Self::from_neurons(neurons).with_frozen(self.frozen)
+    }
+
     pub fn forward(&self, x: &[Value]) -> Vec<Value> {
         self.neurons.iter().map(|n| n.forward(x)).collect()
     }
 
+    pub fn forward_eval(&self, x: &[f64]) -> Vec<f64> {
+        self.neurons.iter().map(|n| n.forward_eval(x)).collect()
+    }
+
     pub fn zero_grad(&self) {
         self.neurons.iter().for_each(|n| n.zero_grad());
     }
 
+    /// Gradient descent step on every parameter, unless the layer is frozen.
     pub fn update(&self, learning_rate: f64) {
+        if self.frozen {
+            return;
+        }
         self.neurons.iter().for_each(|n| n.update(learning_rate));
     }
 
+    /// Estimate of the largest singular value of the weight matrix (one row per
+    /// neuron) after `iterations` rounds of power iteration.
+    pub fn spectral_norm(&self, iterations: usize) -> f64 {
+        let w = self.weight_matrix();
+        let nin = w.first().map_or(0, |row| row.len());
+        let mut v = vec![1.0 / (nin as f64).sqrt(); nin];
+        for _ in 0..iterations {
+            let u = normalize(mat_vec(&w, &v));
+            v = normalize(mat_t_vec(&w, &u));
+        }
+        norm(&mat_vec(&w, &v))
+    }
+
+    /// Divides the weights (not the biases) by their estimated spectral norm in place.
+    pub fn spectral_normalize(&self, iterations: usize) {
+        let sigma = self.spectral_norm(iterations);
+        if sigma > 0.0 {
+            self.neurons
+                .iter()
+                .flat_map(|n| n.weights())
+                .for_each(|w| w.set_data(w.data() / sigma));
+        }
+    }
+
+    /// Estimates the largest and smallest singular values of the weight matrix. Their
+    /// ratio approximates the condition number of the layer.
+    ///
+    /// Power iteration on the Gram matrix `G` gives the largest eigenvalue, and power
+    /// iteration on `λ_max·I - G` gives `λ_max - λ_min`; singular values are their roots.
+    pub fn weight_singular_range(&self, iterations: usize) -> (f64, f64) {
+        let w = self.weight_matrix();
+        let gram = gram_matrix(&w);
+        let lambda_max = largest_eigenvalue(&gram, iterations);
+        let shifted: Vec<Vec<f64>> = gram
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(|(j, g)| if i == j { lambda_max - g } else { -g })
+                    .collect()
+            })
+            .collect();
+        let lambda_min = lambda_max - largest_eigenvalue(&shifted, iterations);
+        (lambda_max.max(0.0).sqrt(), lambda_min.max(0.0).sqrt())
+    }
+
+    /// Rescales, in place, the weights (not the bias) of every neuron whose weight vector
+    /// has an L2 norm above `max_norm` so that its norm is exactly `max_norm`.
+    pub fn max_norm_constraint(&self, max_norm: f64) {
+        for n in &self.neurons {
+            let norm = n
+                .weights()
+                .iter()
+                .map(|w| w.data().powi(2))
+                .sum::<f64>()
+                .sqrt();
+            if norm > max_norm {
+                n.weights()
+                    .iter()
+                    .for_each(|w| w.set_data(w.data() * max_norm / norm));
+            }
+        }
+    }
+
+    /// Index pairs `(i, j)`, `i < j`, of neurons whose weight vectors are within
+    /// Euclidean distance `eps` of each other, a sign that symmetry was never broken.
+    pub fn duplicate_neuron_pairs(&self, eps: f64) -> Vec<(usize, usize)> {
+        let w = self.weight_matrix();
+        let mut pairs = vec![];
+        for i in 0..w.len() {
+            for j in i + 1..w.len() {
+                let diff: Vec<f64> = w[i].iter().zip(&w[j]).map(|(a, b)| a - b).collect();
+                if norm(&diff) <= eps {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn weight_matrix(&self) -> Vec<Vec<f64>> {
+        self.neurons
+            .iter()
+            .map(|n| n.weights().iter().map(|w| w.data()).collect())
+            .collect()
+    }
+
     pub fn len(&self) -> usize {
         self.neurons.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.neurons.is_empty()
+    }
+}
+
+/// Applies `layer` to every step of a sequence, feeding it the step input followed by
+/// the previous state (`init_state` for the first step), and returns the state after
+/// each step. Every step reuses the same weight `Value`s, so `backward` sums their
+/// gradients over all steps.
+pub fn recurrent_apply(
+    layer: &Layer,
+    inputs: &[Vec<Value>],
+    init_state: Vec<Value>,
+) -> Vec<Vec<Value>> {
+    let mut state = init_state;
+    let mut states = Vec::with_capacity(inputs.len());
+    for x in inputs {
+        let step: Vec<Value> = x.iter().cloned().chain(state).collect();
+        state = layer.forward(&step);
+        states.push(state.clone());
+    }
+    states
+}
+
+fn mat_vec(w: &[Vec<f64>], v: &[f64]) -> Vec<f64> {
+    w.iter()
+        .map(|row| row.iter().zip(v).map(|(a, b)| a * b).sum())
+        .collect()
+}
+
+fn mat_t_vec(w: &[Vec<f64>], u: &[f64]) -> Vec<f64> {
+    let nin = w.first().map_or(0, |row| row.len());
+    (0..nin)
+        .map(|j| w.iter().zip(u).map(|(row, ui)| row[j] * ui).sum())
+        .collect()
+}
+
+/// `W·Wᵀ` or `Wᵀ·W`, whichever is smaller; both share the non-zero eigenvalues.
+fn gram_matrix(w: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let nin = w.first().map_or(0, |row| row.len());
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>();
+    if w.len() <= nin {
+        w.iter()
+            .map(|a| w.iter().map(|b| dot(a, b)).collect())
+            .collect()
+    } else {
+        let cols: Vec<Vec<f64>> = (0..nin)
+            .map(|j| w.iter().map(|row| row[j]).collect())
+            .collect();
+        cols.iter()
+            .map(|a| cols.iter().map(|b| dot(a, b)).collect())
+            .collect()
+    }
+}
+
+/// Rayleigh quotient after `iterations` steps of power iteration on a symmetric matrix.
+fn largest_eigenvalue(m: &[Vec<f64>], iterations: usize) -> f64 {
+    let n = m.len();
+    if n == 0 {
+        return 0.0;
+    }
+    let mut v = normalize((1..=n).map(|i| i as f64).collect());
+    for _ in 0..iterations {
+        v = normalize(mat_vec(m, &v));
+    }
+    mat_vec(m, &v).iter().zip(&v).map(|(a, b)| a * b).sum()
+}
+
+fn norm(v: &[f64]) -> f64 {
+    v.iter().map(|e| e * e).sum::<f64>().sqrt()
+}
+
+fn normalize(v: Vec<f64>) -> Vec<f64> {
+    let n = norm(&v);
+    if n > 0.0 {
+        v.into_iter().map(|e| e / n).collect()
+    } else {
+        v
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::layer::Layer;
+    use crate::layer::{recurrent_apply, Layer};
+    use crate::value::Value;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn set_weights(layer: &Layer, rows: &[&[f64]]) {
+        for (n, row) in layer.neurons().iter().zip(rows) {
+            n.weights()
+                .iter()
+                .zip(row.iter())
+                .for_each(|(w, d)| w.set_data(*d));
+        }
+    }
 
     #[test]
     fn parameters() {
@@ -48,4 +281,94 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn weight_singular_range() {
+        let layer = Layer::new(3, 3, true);
+        set_weights(
+            &layer,
+            &[&[1.0, 0.0, 0.0], &[0.0, 1.0, 0.0], &[0.0, 0.0, 1.0]],
+        );
+        let (max, min) = layer.weight_singular_range(50);
+        assert_approx_eq!(max / min, 1.0);
+
+        set_weights(
+            &layer,
+            &[&[4.0, 0.0, 0.0], &[0.0, 2.0, 0.0], &[0.0, 0.0, 0.5]],
+        );
+        let (max, min) = layer.weight_singular_range(200);
+        assert_approx_eq!(max, 4.0, 1e-6);
+        assert_approx_eq!(min, 0.5, 1e-6);
+    }
+
+    #[test]
+    fn duplicate_neuron_pairs() {
+        let layer = Layer::new(2, 4, true);
+        set_weights(
+            &layer,
+            &[&[0.5, -0.2], &[0.1, 0.9], &[0.5, -0.2], &[0.5000001, -0.2]],
+        );
+        assert_eq!(layer.duplicate_neuron_pairs(1e-12), vec![(0, 2)]);
+        assert_eq!(
+            layer.duplicate_neuron_pairs(1e-3),
+            vec![(0, 2), (0, 3), (2, 3)]
+        );
+    }
+
+    #[test]
+    fn max_norm_constraint() {
+        let layer = Layer::new(2, 2, true);
+        set_weights(&layer, &[&[3.0, 4.0], &[0.3, 0.4]]);
+        let bias = layer.neurons()[0].bias().data();
+        layer.max_norm_constraint(1.0);
+
+        let w = layer.neurons()[0].weights();
+        assert_approx_eq!(w[0].data(), 0.6);
+        assert_approx_eq!(w[1].data(), 0.8);
+        assert_eq!(layer.neurons()[0].bias().data(), bias);
+        // neurons within the cap are left alone
+        assert_eq!(layer.neurons()[1].weights()[0].data(), 0.3);
+        assert_eq!(layer.neurons()[1].weights()[1].data(), 0.4);
+    }
+
+    #[test]
+    fn spectral_normalize() {
+        let layer = Layer::new(2, 3, true);
+        set_weights(&layer, &[&[3.0, 0.0], &[0.0, 1.0], &[0.0, 0.0]]);
+        assert_approx_eq!(layer.spectral_norm(20), 3.0);
+
+        layer.spectral_normalize(20);
+        assert_approx_eq!(layer.spectral_norm(20), 1.0);
+        assert_approx_eq!(layer.neurons()[1].weights()[1].data(), 1.0 / 3.0);
+
+        let layer = Layer::new(4, 3, true);
+        layer.spectral_normalize(200);
+        assert_approx_eq!(layer.spectral_norm(200), 1.0, 1e-3);
+    }
+
+    #[test]
+    fn recurrent() {
+        // h_t = 0.5 * x_t + 0.2 * h_(t-1) + 0.1
+        let layer = Layer::new(2, 1, false);
+        set_weights(&layer, &[&[0.5, 0.2]]);
+        layer.neurons()[0].bias().set_data(0.1);
+        let inputs: Vec<Vec<Value>> = [1.0, 2.0, 3.0]
+            .iter()
+            .map(|x| vec![Value::new(*x)])
+            .collect();
+
+        let states = recurrent_apply(&layer, &inputs, vec![Value::new(0.0)]);
+        let loss: Value = states.iter().map(|h| h[0].clone()).sum();
+        loss.backward();
+
+        let h: Vec<f64> = states.iter().map(|h| h[0].data()).collect();
+        assert_approx_eq!(h[0], 0.6);
+        assert_approx_eq!(h[1], 1.22);
+        assert_approx_eq!(h[2], 1.844);
+        // dL/dh = [1.24, 1.2, 1.0], summed over the steps
+        let params = layer.parameters();
+        assert_approx_eq!(params[0].grad(), 1.0 * 1.24 + 2.0 * 1.2 + 3.0 * 1.0);
+        assert_approx_eq!(params[1].grad(), 0.0 * 1.24 + 0.6 * 1.2 + 1.22 * 1.0);
+        assert_approx_eq!(params[2].grad(), 1.24 + 1.2 + 1.0);
+    }
 }
@@ -0,0 +1,20 @@
+pub mod bench;
+pub mod data;
+pub mod error;
+pub mod forward;
+pub mod layer;
+pub mod loss;
+pub mod metrics;
+pub mod mlp;
+pub mod neuron;
+pub mod norm;
+pub mod numeric;
+pub mod optim;
+pub mod train;
+pub mod value;
+pub mod view;
+
+/// Smallest input accepted by ops with a singularity at zero (e.g. `Value::ln`,
+/// `Value::sqrt`); smaller inputs are clamped to it so neither the data nor the
+/// gradient becomes infinite. Each such op has an `_eps` variant to override it.
+pub const DEFAULT_EPS: f64 = 1e-12;
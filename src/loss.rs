@@ -0,0 +1,354 @@
+use crate::value::Value;
+use crate::DEFAULT_EPS;
+
+/// Element-wise difference a - b of two embeddings.
+pub fn sub_vec(a: &[Value], b: &[Value]) -> Vec<Value> {
+    assert_eq!(a.len(), b.len(), "embeddings must have the same size");
+    a.iter()
+        .zip(b)
+        .map(|(ai, bi)| ai.clone() - bi.clone())
+        .collect()
+}
+
+/// Euclidean distance sqrt(sum((a - b)^2)) between two embeddings.
+pub fn euclidean_distance(a: &[Value], b: &[Value]) -> Value {
+    sub_vec(a, b)
+        .iter()
+        .map(|d| d.pow(&Value::new(2.0)))
+        .sum::<Value>()
+        .sqrt()
+}
+
+/// Matrix of Euclidean distances between every pair of `embeddings`. The diagonal is
+/// the constant 0, as sqrt has no gradient at zero distance.
+pub fn pairwise_distances(embeddings: &[Vec<Value>]) -> Vec<Vec<Value>> {
+    let n = embeddings.len();
+    let mut distances = vec![vec![Value::new(0.0); n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let d = euclidean_distance(&embeddings[i], &embeddings[j]);
+            distances[i][j] = d.clone();
+            distances[j][i] = d;
+        }
+    }
+    distances
+}
+
+/// Margin-based contrastive loss between two embeddings with squared Euclidean
+/// distance d^2:
+///
+/// label * d^2 + (1 - label) * max(0, margin - d)^2
+///
+/// Similar pairs (label 1) are pulled together, dissimilar pairs (label 0) are pushed
+/// apart until they are at least `margin` away from each other.
+pub fn contrastive_loss(a: &[Value], b: &[Value], label: f64, margin: f64) -> Value {
+    assert_eq!(a.len(), b.len(), "embeddings must have the same size");
+    let squared: Value = a
+        .iter()
+        .zip(b)
+        .map(|(ai, bi)| (ai.clone() - bi.clone()).pow(&Value::new(2.0)))
+        .sum();
+    let hinge = (Value::new(margin) - squared.sqrt()).max(&Value::new(0.0));
+    Value::new(label) * squared + Value::new(1.0 - label) * hinge.pow(&Value::new(2.0))
+}
+
+/// Squared error (pred - target)^2, averaged over examples this is the MSE.
+pub fn squared_error(pred: &Value, target: f64) -> Value {
+    (pred.clone() - Value::new(target)).pow(&Value::new(2.0))
+}
+
+/// Huber loss of the error x = pred - target: 0.5 * x^2 for |x| <= delta and
+/// delta * (|x| - 0.5 * delta) beyond, quadratic near the target and linear for outliers.
+pub fn huber_loss(pred: &Value, target: f64, delta: f64) -> Value {
+    let x = pred.clone() - Value::new(target);
+    let d = x.data();
+    if d.abs() <= delta {
+        Value::new(0.5) * x.pow(&Value::new(2.0))
+    } else {
+        Value::new(delta * d.signum()) * x - Value::new(0.5 * delta * delta)
+    }
+}
+
+/// PyTorch's `smooth_l1_loss` of the error x = pred - target: 0.5 * x^2 / beta for
+/// |x| < beta and |x| - 0.5 * beta beyond.
+///
+/// This is `huber_loss` with `delta = beta` divided by `beta`, so its slope outside the
+/// quadratic zone is 1 rather than `delta`.
+pub fn smooth_l1(pred: &Value, target: f64, beta: f64) -> Value {
+    let x = pred.clone() - Value::new(target);
+    let d = x.data();
+    if d.abs() < beta {
+        Value::new(0.5 / beta) * x.pow(&Value::new(2.0))
+    } else {
+        Value::new(d.signum()) * x - Value::new(0.5 * beta)
+    }
+}
+
+/// Triplet loss max(0, d(anchor, positive) - d(anchor, negative) + margin) with the
+/// Euclidean distance d.
+///
+/// It pulls the positive toward the anchor and pushes the negative away until the
+/// negative is at least `margin` farther from the anchor than the positive.
+pub fn triplet_loss(
+    anchor: &[Value],
+    positive: &[Value],
+    negative: &[Value],
+    margin: f64,
+) -> Value {
+    let gap = euclidean_distance(anchor, positive) - euclidean_distance(anchor, negative)
+        + Value::new(margin);
+    gap.max(&Value::new(0.0))
+}
+
+/// Hinge loss max(0, 1 - target * pred) for a target in {-1, +1}.
+///
+/// Predictions on the correct side with a margin of at least 1 cost nothing and get no
+/// gradient; all others are pushed toward the target.
+pub fn hinge_loss(pred: &Value, target: f64) -> Value {
+    (Value::new(1.0) - Value::new(target) * pred.clone()).max(&Value::new(0.0))
+}
+
+/// Kullback-Leibler divergence sum(p * ln(p / q)) of `q` from the target distribution `p`,
+/// computed as sum(p * (ln(p) - ln(q))).
+///
+/// Probabilities are clamped to at least `eps` (`DEFAULT_EPS` if `None`) inside the
+/// logarithms, so zero probabilities stay finite. Gradients flow into both `p` and `q`;
+/// pass constant `p` values for soft-label training.
+pub fn kl_divergence(p: &[Value], q: &[Value], eps: Option<f64>) -> Value {
+    assert_eq!(p.len(), q.len(), "distributions must have the same size");
+    let eps = eps.unwrap_or(DEFAULT_EPS);
+    p.iter()
+        .zip(q)
+        .map(|(pi, qi)| pi.clone() * (pi.ln_eps(eps) - qi.ln_eps(eps)))
+        .sum()
+}
+
+/// Focal loss for binary targets in {0, 1}, averaged over the examples:
+///
+/// -alpha * (1 - p_t)^gamma * ln(p_t)
+///
+/// where p_t is the sigmoid of the logit `pred` for a positive target and one minus it
+/// for a negative one. The (1 - p_t)^gamma factor shrinks the loss of well-classified
+/// examples so training focuses on the hard ones; with `gamma = 0` this is binary
+/// cross-entropy weighted by `alpha`. Both p_t inside the logarithm and 1 - p_t inside
+/// the power are clamped to at least `eps` (`DEFAULT_EPS` if `None`), so saturated
+/// logits keep finite gradients for any gamma.
+pub fn focal_loss(
+    pred: &[Value],
+    target: &[f64],
+    gamma: f64,
+    alpha: f64,
+    eps: Option<f64>,
+) -> Value {
+    assert_eq!(pred.len(), target.len(), "one target per prediction");
+    let eps = eps.unwrap_or(DEFAULT_EPS);
+    let n = pred.len() as f64;
+    pred.iter()
+        .zip(target)
+        .map(|(logit, &t)| {
+            let p = logit.sigmoid();
+            let p_t = if t > 0.5 { p } else { Value::new(1.0) - p };
+            let modulation = (Value::new(1.0) - p_t.clone())
+                .clamp(eps, 1.0)
+                .pow(&Value::new(gamma));
+            Value::new(-alpha / n) * modulation * p_t.ln_eps(eps)
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::softmax;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn embedding(data: &[f64]) -> Vec<Value> {
+        data.iter().map(|d| Value::new(*d)).collect()
+    }
+
+    #[test]
+    fn pairwise() {
+        let embeddings = vec![
+            embedding(&[0.0, 0.0]),
+            embedding(&[3.0, 4.0]),
+            embedding(&[1.0, 1.0]),
+        ];
+        let d = pairwise_distances(&embeddings);
+        for (i, row) in d.iter().enumerate() {
+            assert_eq!(row[i].data(), 0.0);
+            for (j, dij) in row.iter().enumerate() {
+                assert_eq!(dij.data(), d[j][i].data());
+            }
+        }
+        assert_approx_eq!(d[0][1].data(), 5.0);
+        assert_approx_eq!(d[0][2].data(), 2f64.sqrt());
+        assert_approx_eq!(d[1][2].data(), 13f64.sqrt());
+
+        d[0][1].backward();
+        assert_approx_eq!(embeddings[1][0].grad(), 0.6);
+        assert_approx_eq!(embeddings[0][1].grad(), -0.8);
+    }
+
+    #[test]
+    fn contrastive() {
+        let a = embedding(&[0.1, 0.2]);
+        let b = embedding(&[0.15, 0.2]);
+        let similar = contrastive_loss(&a, &b, 1.0, 1.0);
+        let dissimilar = contrastive_loss(&a, &b, 0.0, 1.0);
+        assert!(similar.data() < 0.01);
+        assert!(dissimilar.data() > 0.8);
+
+        // gradient descent moves a away from b
+        dissimilar.backward();
+        assert!(a[0].grad() > 0.0);
+        assert!(b[0].grad() < 0.0);
+
+        // dissimilar pairs beyond the margin cost nothing
+        let far = embedding(&[2.0, 2.0]);
+        assert_eq!(contrastive_loss(&a, &far, 0.0, 1.0).data(), 0.0);
+    }
+
+    #[test]
+    fn squared() {
+        let pred = Value::new(0.5);
+        let loss = squared_error(&pred, 2.0);
+        loss.backward();
+        assert_eq!(loss.data(), 2.25);
+        assert_eq!(pred.grad(), -3.0);
+    }
+
+    #[test]
+    fn smooth_l1_matches_huber() {
+        let beta = 0.5;
+        for error in [-2.0, -0.3, 0.1, 0.5, 3.0] {
+            let a = Value::new(1.0 + error);
+            let b = Value::new(1.0 + error);
+            let smooth = smooth_l1(&a, 1.0, beta);
+            let huber = huber_loss(&b, 1.0, beta);
+            assert_approx_eq!(smooth.data(), huber.data() / beta);
+            smooth.backward();
+            huber.backward();
+            assert_approx_eq!(a.grad(), b.grad() / beta);
+        }
+
+        // value and slope are continuous at |x| = beta
+        let at = |x: f64| {
+            let pred = Value::new(x);
+            let loss = smooth_l1(&pred, 0.0, beta);
+            loss.backward();
+            (loss.data(), pred.grad())
+        };
+        let (inside, outside) = (at(beta - 1e-9), at(beta));
+        assert_approx_eq!(inside.0, outside.0, 1e-8);
+        assert_approx_eq!(inside.1, outside.1, 1e-8);
+        assert_eq!(outside.1, 1.0);
+    }
+
+    #[test]
+    fn triplet() {
+        let anchor = embedding(&[0.0, 0.0]);
+        let positive = embedding(&[1.0, 0.0]);
+        let negative = embedding(&[3.0, 0.0]);
+        let loss = triplet_loss(&anchor, &positive, &negative, 1.0);
+        loss.backward();
+        assert_eq!(loss.data(), 0.0);
+        assert!(positive.iter().chain(&negative).all(|v| v.grad() == 0.0));
+
+        let negative = embedding(&[1.5, 0.0]);
+        let loss = triplet_loss(&anchor, &positive, &negative, 1.0);
+        loss.backward();
+        assert_approx_eq!(loss.data(), 0.5);
+        // gradient descent moves the positive toward the anchor and the negative away
+        assert!(positive[0].grad() > 0.0);
+        assert!(negative[0].grad() < 0.0);
+    }
+
+    #[test]
+    fn hinge() {
+        let confident = Value::new(1.5);
+        let loss = hinge_loss(&confident, 1.0);
+        loss.backward();
+        assert_eq!(loss.data(), 0.0);
+        assert_eq!(confident.grad(), 0.0);
+
+        let wrong = Value::new(0.5);
+        let loss = hinge_loss(&wrong, -1.0);
+        loss.backward();
+        assert_eq!(loss.data(), 1.5);
+        // gradient descent lowers the prediction toward -1
+        assert_eq!(wrong.grad(), 1.0);
+
+        let within_margin = Value::new(0.25);
+        let loss = hinge_loss(&within_margin, 1.0);
+        loss.backward();
+        assert_eq!(loss.data(), 0.75);
+        assert_eq!(within_margin.grad(), -1.0);
+    }
+
+    #[test]
+    fn focal() {
+        let sigmoid = |x: f64| 1.0 / (1.0 + (-x).exp());
+
+        // gamma = 0 is alpha-weighted binary cross-entropy
+        let logits = embedding(&[2.0, -0.5]);
+        let loss = focal_loss(&logits, &[1.0, 0.0], 0.0, 0.25, None);
+        let bce = -(sigmoid(2.0).ln() + (1.0 - sigmoid(-0.5)).ln()) / 2.0;
+        assert_approx_eq!(loss.data(), 0.25 * bce);
+        // the BCE gradient with respect to a logit is (p - target) / n
+        loss.backward();
+        assert_approx_eq!(logits[0].grad(), 0.25 * (sigmoid(2.0) - 1.0) / 2.0);
+        assert_approx_eq!(logits[1].grad(), 0.25 * sigmoid(-0.5) / 2.0);
+
+        // an easy (confidently correct) example gets less gradient than a hard one
+        let easy = Value::new(3.0);
+        let hard = Value::new(-1.0);
+        focal_loss(&[easy.clone(), hard.clone()], &[1.0, 1.0], 2.0, 1.0, None).backward();
+        assert!(hard.grad() < 0.0);
+        assert!(easy.grad().abs() < 0.01 * hard.grad().abs());
+
+        // a saturated wrong prediction costs at most -alpha * ln(eps)
+        let wrong = focal_loss(&embedding(&[-1000.0]), &[1.0], 0.0, 1.0, Some(1e-4));
+        assert_approx_eq!(wrong.data(), -(1e-4f64).ln());
+
+        // a saturated correct prediction has p_t = 1, where (1 - p_t)^gamma has no
+        // finite slope for gamma < 1
+        for gamma in [0.0, 0.5] {
+            let logits = embedding(&[40.0, -40.0]);
+            let loss = focal_loss(&logits, &[1.0, 0.0], gamma, 0.25, None);
+            loss.backward();
+            assert_approx_eq!(loss.data(), 0.0);
+            assert!(logits.iter().all(|l| l.grad().is_finite()));
+        }
+    }
+
+    #[test]
+    fn kl() {
+        let p = embedding(&[0.7, 0.2, 0.1]);
+        assert_approx_eq!(
+            kl_divergence(&p, &embedding(&[0.7, 0.2, 0.1]), None).data(),
+            0.0
+        );
+
+        let logits = embedding(&[0.0, 0.5, 1.0]);
+        let q = softmax(&logits, 1.0);
+        let kl = kl_divergence(&p, &q, None);
+        assert!(kl.data() > 0.0);
+
+        // through a softmax the logit gradient is q - p, so descent moves q toward p
+        kl.backward();
+        for i in 0..3 {
+            assert_approx_eq!(logits[i].grad(), q[i].data() - p[i].data());
+        }
+        assert!(logits[0].grad() < 0.0);
+        assert!(logits[2].grad() > 0.0);
+
+        let with_zero = kl_divergence(&embedding(&[1.0, 0.0]), &embedding(&[0.5, 0.5]), None);
+        assert_approx_eq!(with_zero.data(), 2f64.ln());
+
+        // a zero in q costs ln(1 / eps)
+        let p = embedding(&[1.0, 0.0]);
+        let q = embedding(&[0.0, 1.0]);
+        assert_approx_eq!(kl_divergence(&p, &q, None).data(), -DEFAULT_EPS.ln());
+        assert_approx_eq!(kl_divergence(&p, &q, Some(1e-3)).data(), -(1e-3f64).ln());
+    }
+}
@@ -1,13 +1,8 @@
-use crate::mlp::Mlp;
-use crate::neuron::Neuron;
-use crate::value::Value;
-use crate::view::print_computation_graph;
-
-mod layer;
-mod mlp;
-mod neuron;
-mod value;
-mod view;
+use micrograd::mlp::Mlp;
+use micrograd::neuron::Neuron;
+use micrograd::train::TrainConfig;
+use micrograd::value::Value;
+use micrograd::view::print_computation_graph;
 
 fn main() {
     let a = Value::new(2.0).with_label("a");
@@ -56,7 +51,7 @@ fn main() {
 
     let mlp = Mlp::new(3, vec![4, 4, 1], true);
     println!("{}", mlp.stat());
-    mlp.train(xs, ys, 20, 0.1);
+    mlp.train(xs, ys, &TrainConfig::new(20, 0.1));
     let pred = mlp.forward(vec![2.0, 3.0, -1.0].into_iter().map(Value::new).collect());
     println!("Prediction: {pred:?}");
     println!("{}", print_computation_graph(&pred[0], Some("pred.svg")));
@@ -0,0 +1,56 @@
+use crate::value::Value;
+use crate::DEFAULT_EPS;
+
+/// Shannon entropy -sum(p * ln(p)) of a probability vector such as a `softmax` output.
+///
+/// Probabilities are clamped to at least `eps` (`DEFAULT_EPS` if `None`) inside the
+/// logarithm, so zero probabilities contribute zero instead of NaN.
+pub fn entropy(probs: &[Value], eps: Option<f64>) -> Value {
+    let eps = eps.unwrap_or(DEFAULT_EPS);
+    let sum: Value = probs.iter().map(|p| p.clone() * p.ln_eps(eps)).sum();
+    Value::new(-1.0) * sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::softmax;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn entropy_bounds() {
+        let uniform: Vec<Value> = (0..4).map(|_| Value::new(0.25)).collect();
+        assert_approx_eq!(entropy(&uniform, None).data(), 4f64.ln());
+
+        let one_hot: Vec<Value> = [0.0, 1.0, 0.0, 0.0]
+            .iter()
+            .map(|p| Value::new(*p))
+            .collect();
+        assert_approx_eq!(entropy(&one_hot, None).data(), 0.0);
+
+        let skewed: Vec<Value> = [0.7, 0.1, 0.1, 0.1]
+            .iter()
+            .map(|p| Value::new(*p))
+            .collect();
+        let h = entropy(&skewed, None).data();
+        assert!(h > 0.0 && h < 4f64.ln());
+
+        // probabilities below eps are clamped inside the logarithm only
+        let tiny: Vec<Value> = [0.05, 0.95].iter().map(|p| Value::new(*p)).collect();
+        assert_approx_eq!(
+            entropy(&tiny, Some(0.1)).data(),
+            -(0.05 * 0.1f64.ln() + 0.95 * 0.95f64.ln())
+        );
+    }
+
+    #[test]
+    fn entropy_gradient() {
+        let logits = vec![Value::new(2.0), Value::new(0.0), Value::new(-1.0)];
+        let h = entropy(&softmax(&logits, 1.0), None);
+        h.backward();
+
+        // raising the dominant logit makes the distribution more certain
+        assert!(logits[0].grad() < 0.0);
+        assert!(logits.iter().all(|l| l.grad().is_finite()));
+    }
+}
@@ -1,10 +1,26 @@
+use crate::data::shuffle_indices;
+use crate::error::{Error, Result};
 use crate::layer::Layer;
-use crate::value::Value;
+use crate::neuron::{Activation, Neuron, TanhImpl};
+use crate::optim::{clip_grad_value, Optimizer};
+use crate::train::{EpochMetric, TrainConfig, TrainingConfig};
+use crate::value::{nodes_created, Value};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
+use std::fs::{read_to_string, write};
+use std::time::{Duration, Instant};
 
 #[derive(Clone, Debug)]
 pub struct Mlp {
     layers: Vec<Layer>,
+    quantization_error: Cell<f64>,
+    max_nodes: Option<usize>,
+    dropout: f64,
+    activation_limit: Option<f64>,
 }
 
 #[derive(Debug)]
@@ -14,6 +30,70 @@ pub struct MlpStat {
     num_weights: usize,
     num_biases: usize,
     num_parameters: usize,
+    per_layer: Vec<LayerStat>,
+}
+
+#[derive(Debug)]
+pub struct LayerStat {
+    num_neurons: usize,
+    num_weights: usize,
+    num_biases: usize,
+}
+
+/// Statistics of the network outputs over a dataset.
+#[derive(Debug)]
+pub struct OutputStats {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std: f64,
+}
+
+/// Health snapshot of the gradients and parameters on a dataset.
+#[derive(Debug)]
+pub struct StabilityReport {
+    pub grad_norm: f64,
+    pub param_norm: f64,
+    /// Number of parameters whose data is NaN or infinite.
+    pub non_finite_params: usize,
+    /// `grad_norm / param_norm`, a heuristic for the relative size of an update.
+    pub grad_to_param_ratio: f64,
+}
+
+/// Coarse timing of a single forward and backward pass.
+#[derive(Debug)]
+pub struct ProfileReport {
+    pub forward: Duration,
+    pub backward: Duration,
+    /// Number of nodes in the graph that was differentiated.
+    pub nodes: usize,
+}
+
+/// A linear layer as exported from PyTorch: `weight` is `[out][in]`.
+#[derive(Deserialize)]
+struct TorchLayer {
+    weight: Vec<Vec<f64>>,
+    bias: Vec<f64>,
+}
+
+#[derive(Deserialize)]
+struct TorchMlp {
+    layers: Vec<TorchLayer>,
+}
+
+/// File format of `Mlp::save`: `weight` is `[out][in]` like in `TorchLayer`.
+#[derive(Serialize, Deserialize)]
+struct SavedLayer {
+    weight: Vec<Vec<f64>>,
+    bias: Vec<f64>,
+    activation: Activation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedMlp {
+    layers: Vec<SavedLayer>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    training: Option<TrainingConfig>,
 }
 
 impl Display for MlpStat {
@@ -24,6 +104,19 @@ impl Display for MlpStat {
         writeln!(f, "  Number of Weights: {}", self.num_weights)?;
         writeln!(f, "  Number of Biases: {}", self.num_biases)?;
         writeln!(f, "  Total Number of Parameters: {}", self.num_parameters)?;
+        writeln!(f, "  Per Layer:")?;
+        writeln!(
+            f,
+            "    {:>5} {:>8} {:>8} {:>8}",
+            "layer", "neurons", "weights", "biases"
+        )?;
+        for (i, layer) in self.per_layer.iter().enumerate() {
+            writeln!(
+                f,
+                "    {:>5} {:>8} {:>8} {:>8}",
+                i, layer.num_neurons, layer.num_weights, layer.num_biases
+            )?;
+        }
         Ok(())
     }
 }
@@ -40,7 +133,355 @@ impl Mlp {
                 )
             })
             .collect();
-        Self { layers }
+        Self::from_layers(layers)
+    }
+
+    /// Builds a network from explicit `(weight rows, biases, activation)` per layer,
+    /// where row `j` holds the input weights of neuron `j`. Fails if the shapes of
+    /// consecutive layers do not line up.
+    pub fn from_weights(layers: Vec<(Vec<Vec<f64>>, Vec<f64>, Activation)>) -> Result<Self> {
+        let mut nin = None;
+        for (i, (rows, biases, _)) in layers.iter().enumerate() {
+            if rows.len() != biases.len() {
+                return Err(Error::Shape(format!(
+                    "layer {} has {} weight rows but {} biases",
+                    i,
+                    rows.len(),
+                    biases.len()
+                )));
+            }
+            for row in rows {
+                if *nin.get_or_insert(row.len()) != row.len() {
+                    return Err(Error::Shape(format!(
+                        "layer {} expects {} inputs, got a weight row of length {}",
+                        i,
+                        nin.unwrap(),
+                        row.len()
+                    )));
+                }
+            }
+            nin = Some(rows.len());
+        }
+        let layers = layers
+            .into_iter()
+            .map(|(rows, biases, activation)| {
+                Layer::from_neurons(
+                    rows.into_iter()
+                        .zip(biases)
+                        .map(|(row, bias)| Neuron::from_weights(row, bias, activation))
+                        .collect(),
+                )
+            })
+            .collect();
+        Ok(Self::from_layers(layers))
+    }
+
+    fn from_layers(layers: Vec<Layer>) -> Self {
+        Self {
+            layers,
+            quantization_error: Cell::new(0.0),
+            max_nodes: None,
+            dropout: 0.0,
+            activation_limit: None,
+        }
+    }
+
+    /// Selects how every layer computes tanh; `TanhImpl::Exact` by default.
+    pub fn with_tanh_impl(mut self, tanh: TanhImpl) -> Self {
+        self.layers = self
+            .layers
+            .into_iter()
+            .map(|l| l.with_tanh_impl(tanh))
+            .collect();
+        self
+    }
+
+    /// Freezes the layers at the given indices, see `Layer::with_frozen`. `train` drops
+    /// their gradients and leaves their parameters out of the penalties, the optimizer
+    /// and the max-norm constraint, so they keep their values; `effective_parameters`
+    /// does not count them.
+    pub fn with_frozen_layers(mut self, layers: &[usize]) -> Self {
+        for &i in layers {
+            self.layers[i] = self.layers[i].clone().with_frozen(true);
+        }
+        self
+    }
+
+    /// Zeroes each hidden activation with probability `p` during `train` (and in
+    /// `mc_dropout_predict`), scaling the kept ones by 1 / (1 - p). Inference through
+    /// `forward`/`predict` is unaffected.
+    pub fn with_dropout(mut self, p: f64) -> Self {
+        assert!(
+            (0.0..1.0).contains(&p),
+            "dropout probability must be in [0, 1)"
+        );
+        self.dropout = p;
+        self
+    }
+
+    /// `forward` with dropout applied to the outputs of every hidden layer.
+    fn forward_dropout(&self, mut x: Vec<Value>, rng: &mut impl Rng) -> Vec<Value> {
+        if self.dropout == 0.0 {
+            return self.forward(x);
+        }
+        for (i, layer) in self.layers.iter().enumerate() {
+            x = layer.forward(&x);
+            if i + 1 < self.layers.len() {
+                x = x
+                    .into_iter()
+                    .map(|a| a * Value::new(self.dropout_mask(rng)))
+                    .collect();
+            }
+        }
+        x
+    }
+
+    /// 0 with probability `dropout`, otherwise the inverted-dropout scale 1 / (1 - p).
+    fn dropout_mask(&self, rng: &mut impl Rng) -> f64 {
+        if rng.random::<f64>() < self.dropout {
+            0.0
+        } else {
+            1.0 / (1.0 - self.dropout)
+        }
+    }
+
+    /// Makes `forward_checked` fail as soon as an activation exceeds `limit` in
+    /// magnitude, before the instability turns into NaN gradients.
+    pub fn with_activation_limit(mut self, limit: f64) -> Self {
+        self.activation_limit = Some(limit);
+        self
+    }
+
+    /// Makes `forward` panic once a single pass has created more than `max_nodes` graph
+    /// nodes, to catch runaway graph construction early. Unlimited by default.
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    /// All parameters in a fixed order: layers in network order, neurons in layer order,
+    /// and for each neuron its weights followed by its bias. Serialization and
+    /// averaging rely on this order, so it must stay deterministic.
+    pub fn parameters(&self) -> Vec<Value> {
+        self.layers.iter().flat_map(|l| l.parameters()).collect()
+    }
+
+    /// Re-initializes every parameter in place and zeroes the gradients, so the same
+    /// network can be reused across trials. A seed makes the new weights reproducible.
+    pub fn reset(&self, seed: Option<u64>) {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+        self.layers.iter().for_each(|l| l.reset(&mut rng));
+    }
+
+    /// A copy of the network backed by new parameter nodes, unlike `clone`
+    /// which shares the parameters with the original.
+    pub fn deep_copy(&self) -> Self {
+        Self {
+            layers: self.layers.iter().map(|l| l.deep_copy()).collect(),
+            quantization_error: self.quantization_error.clone(),
+            max_nodes: self.max_nodes,
+            dropout: self.dropout,
+            activation_limit: self.activation_limit,
+        }
+    }
+
+    /// A new network whose parameters are the mean of the corresponding parameters
+    /// of `models`, which must all share the same architecture.
+    pub fn average(models: &[&Mlp]) -> Mlp {
+        assert!(!models.is_empty(), "cannot average an empty set of models");
+        assert!(
+            models.iter().all(|m| m.shape() == models[0].shape()),
+            "models must have identical architectures"
+        );
+
+        let params: Vec<Vec<Value>> = models.iter().map(|m| m.parameters()).collect();
+        let out = models[0].deep_copy();
+        for (i, p) in out.parameters().iter().enumerate() {
+            let sum: f64 = params.iter().map(|ps| ps[i].data()).sum();
+            p.set_data(sum / models.len() as f64);
+        }
+        out
+    }
+
+    /// Neuron and parameter count of every layer.
+    fn shape(&self) -> Vec<(usize, usize)> {
+        self.layers
+            .iter()
+            .map(|l| (l.len(), l.parameters().len()))
+            .collect()
+    }
+
+    /// Copies the parameter data of every layer of `source` whose shape matches the layer
+    /// at the same position in this network, and returns the indices of the copied
+    /// layers. Layers that don't match keep their current parameters.
+    pub fn load_compatible_layers(&self, source: &Mlp) -> Vec<usize> {
+        let layer_shape = |l: &Layer| {
+            l.neurons()
+                .iter()
+                .map(|n| n.weights().len())
+                .collect::<Vec<_>>()
+        };
+        self.layers
+            .iter()
+            .zip(&source.layers)
+            .enumerate()
+            .filter(|(_, (dst, src))| layer_shape(dst) == layer_shape(src))
+            .map(|(i, (dst, src))| {
+                for (d, s) in dst.parameters().iter().zip(src.parameters()) {
+                    d.set_data(s.data());
+                }
+                i
+            })
+            .collect()
+    }
+
+    /// Overwrites the parameters with the weights of a PyTorch model exported as
+    /// `{"layers": [{"weight": [[...]], "bias": [...]}]}`.
+    ///
+    /// The network must already have the same architecture as the exported one.
+    pub fn load_torch_json(&self, path: &str) -> Result<()> {
+        let torch: TorchMlp = serde_json::from_str(&read_to_string(path)?)?;
+        if torch.layers.len() != self.layers.len() {
+            return Err(Error::Shape(format!(
+                "expected {} layers, got {}",
+                self.layers.len(),
+                torch.layers.len()
+            )));
+        }
+        for (i, (layer, t)) in self.layers.iter().zip(&torch.layers).enumerate() {
+            let neurons = layer.neurons();
+            let fits = t.weight.len() == neurons.len()
+                && t.bias.len() == neurons.len()
+                && neurons
+                    .iter()
+                    .zip(&t.weight)
+                    .all(|(n, row)| n.weights().len() == row.len());
+            if !fits {
+                return Err(Error::Shape(format!(
+                    "layer {} does not match the exported weights",
+                    i
+                )));
+            }
+        }
+
+        for (layer, t) in self.layers.iter().zip(torch.layers) {
+            for ((neuron, row), bias) in layer.neurons().iter().zip(t.weight).zip(t.bias) {
+                neuron
+                    .weights()
+                    .iter()
+                    .zip(row)
+                    .for_each(|(w, d)| w.set_data(d));
+                neuron.bias().set_data(bias);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the weights, biases and activations to `path` as JSON, optionally
+    /// together with the configuration the model was trained with.
+    pub fn save(&self, path: &str, training: Option<&TrainingConfig>) -> Result<()> {
+        let saved = SavedMlp {
+            layers: self
+                .layers
+                .iter()
+                .map(|l| SavedLayer {
+                    weight: l
+                        .neurons()
+                        .iter()
+                        .map(|n| n.weights().iter().map(|w| w.data()).collect())
+                        .collect(),
+                    bias: l.neurons().iter().map(|n| n.bias().data()).collect(),
+                    activation: l
+                        .neurons()
+                        .first()
+                        .map_or(Activation::Linear, |n| n.activation()),
+                })
+                .collect(),
+            training: training.cloned(),
+        };
+        write(path, serde_json::to_string_pretty(&saved)?)?;
+        Ok(())
+    }
+
+    /// Rust source of a dependency-free `fn predict(x: &[f64]) -> Vec<f64>` computing
+    /// `forward_eval` with the current weights baked in as constants `W{layer}` and
+    /// `B{layer}`. Tanh is always the exact `f64::tanh`.
+    pub fn codegen(&self) -> String {
+        let list = |xs: Vec<String>| xs.join(", ");
+        let mut src = String::from("pub fn predict(x: &[f64]) -> Vec<f64> {\n");
+        for (i, layer) in self.layers.iter().enumerate() {
+            let neurons = layer.neurons();
+            let nin = neurons.first().map_or(0, |n| n.weights().len());
+            let rows = neurons
+                .iter()
+                .map(|n| {
+                    format!(
+                        "[{}]",
+                        list(
+                            n.weights()
+                                .iter()
+                                .map(|w| format!("{:?}", w.data()))
+                                .collect()
+                        )
+                    )
+                })
+                .collect();
+            let biases = neurons
+                .iter()
+                .map(|n| format!("{:?}", n.bias().data()))
+                .collect();
+            src += &format!(
+                "    const W{i}: [[f64; {nin}]; {nout}] = [{}];\n    const B{i}: [f64; {nout}] = [{}];\n",
+                list(rows),
+                list(biases),
+                nout = neurons.len(),
+            );
+            src += &format!(
+                "    let x: Vec<f64> = W{i}\n        .iter()\n        .zip(B{i})\n        .map(|(w, b)| w.iter().zip(x.iter()).map(|(w, x)| w * x).sum::<f64>() + b)\n        .collect();\n"
+            );
+            let tanh: Vec<bool> = neurons
+                .iter()
+                .map(|n| n.activation() == Activation::Tanh)
+                .collect();
+            if tanh.iter().all(|t| *t) {
+                src += "    let x: Vec<f64> = x.iter().map(|z| z.tanh()).collect();\n";
+            } else if tanh.iter().any(|t| *t) {
+                src += &format!(
+                    "    const TANH{i}: [bool; {}] = [{}];\n    let x: Vec<f64> = x.iter().zip(TANH{i}).map(|(z, t)| if t {{ z.tanh() }} else {{ *z }}).collect();\n",
+                    tanh.len(),
+                    list(tanh.iter().map(|t| t.to_string()).collect()),
+                );
+            }
+        }
+        src += "    x.to_vec()\n}\n";
+        src
+    }
+
+    /// Reads a model written by `save`, with its training configuration if one was saved.
+    pub fn load(path: &str) -> Result<(Self, Option<TrainingConfig>)> {
+        let saved: SavedMlp = serde_json::from_str(&read_to_string(path)?)?;
+        let mlp = Self::from_weights(
+            saved
+                .layers
+                .into_iter()
+                .map(|l| (l.weight, l.bias, l.activation))
+                .collect(),
+        )?;
+        Ok((mlp, saved.training))
+    }
+
+    pub fn for_each_layer(&self, mut f: impl FnMut(usize, &Layer)) {
+        self.layers.iter().enumerate().for_each(|(i, l)| f(i, l));
+    }
+
+    /// Replaces the data of every parameter with `f(data)`.
+    pub fn map_parameters(&self, mut f: impl FnMut(f64) -> f64) {
+        self.parameters()
+            .iter()
+            .for_each(|p| p.set_data(f(p.data())));
     }
 
     pub fn zero_grad(&self) {
@@ -51,7 +492,214 @@ impl Mlp {
         self.layers.iter().for_each(|l| l.update(learning_rate));
     }
 
-    pub fn train(&self, xs: Vec<Vec<f64>>, ys: Vec<f64>, n: usize, learning_rate: f64) {
+    /// L2 norm of the gradients of all parameters.
+    pub fn grad_norm(&self) -> f64 {
+        self.parameters()
+            .iter()
+            .map(|p| p.grad().powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// The graph of `loss`, for computing gradients outside of `train`.
+    fn loss_graph(&self, xs: &[Vec<f64>], ys: &[f64]) -> Value {
+        xs.iter()
+            .zip(ys)
+            .map(|(x, y)| {
+                let ypred = self.forward(x.iter().map(|e| Value::new(*e)).collect());
+                (ypred[0].clone() - Value::new(*y)).pow(&Value::new(2.0))
+            })
+            .sum()
+    }
+
+    /// Central finite-difference gradients of `loss` on `(xs, ys)`, aligned with
+    /// `parameters()`, for checking the analytic gradients of `backward`.
+    pub fn numerical_gradients(&self, xs: &[Vec<f64>], ys: &[f64], eps: f64) -> Vec<f64> {
+        self.parameters()
+            .iter()
+            .map(|p| {
+                let data = p.data();
+                p.set_data(data + eps);
+                let plus = self.loss(xs, ys);
+                p.set_data(data - eps);
+                let minus = self.loss(xs, ys);
+                p.set_data(data);
+                (plus - minus) / (2.0 * eps)
+            })
+            .collect()
+    }
+
+    /// The current gradients of all parameters, in `parameters()` order.
+    pub fn save_grads(&self) -> Vec<f64> {
+        self.parameters().iter().map(|p| p.grad()).collect()
+    }
+
+    /// Runs `loss.backward()` and returns the gradients of `params` only, in the given
+    /// order. Only `params` are zeroed beforehand, so the other parameters keep
+    /// accumulating as usual.
+    pub fn backward_subset(&self, loss: &Value, params: &[Value]) -> Vec<f64> {
+        params.iter().for_each(|p| p.zero_grad());
+        loss.backward();
+        params.iter().map(|p| p.grad()).collect()
+    }
+
+    /// Cosine similarity of the loss gradients on two datasets, `(xs, ys)` each.
+    /// Negative values mean the tasks pull the parameters in conflicting directions.
+    /// The gradients are zeroed afterwards.
+    pub fn grad_cosine(&self, task_a: (&[Vec<f64>], &[f64]), task_b: (&[Vec<f64>], &[f64])) -> f64 {
+        let [a, b] = [task_a, task_b].map(|(xs, ys)| {
+            self.zero_grad();
+            self.loss_graph(xs, ys).backward();
+            self.save_grads()
+        });
+        self.zero_grad();
+        let dot: f64 = a.iter().zip(&b).map(|(x, y)| x * y).sum();
+        let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+        dot / (norm(&a) * norm(&b))
+    }
+
+    /// Diagonal of the empirical Fisher information: the squared gradient of each
+    /// example's loss, averaged over `(xs, ys)` and aligned with `parameters()`. This is
+    /// the per-parameter importance used by elastic weight consolidation.
+    pub fn fisher_diagonal(&self, xs: &[Vec<f64>], ys: &[f64]) -> Vec<f64> {
+        let params = self.parameters();
+        let mut fisher = vec![0.0; params.len()];
+        for (x, y) in xs.iter().zip(ys) {
+            self.zero_grad();
+            self.loss_graph(std::slice::from_ref(x), &[*y]).backward();
+            fisher
+                .iter_mut()
+                .zip(&params)
+                .for_each(|(f, p)| *f += p.grad().powi(2) / xs.len() as f64);
+        }
+        self.zero_grad();
+        fisher
+    }
+
+    /// Population variance across `(xs, ys)` of each example's loss gradient, aligned
+    /// with `parameters()`. High values mark parameters the examples disagree on. The
+    /// gradients are zeroed afterwards.
+    pub fn grad_variance(&self, xs: &[Vec<f64>], ys: &[f64]) -> Vec<f64> {
+        let grads: Vec<Vec<f64>> = xs
+            .iter()
+            .zip(ys)
+            .map(|(x, y)| {
+                self.zero_grad();
+                self.loss_graph(std::slice::from_ref(x), &[*y]).backward();
+                self.save_grads()
+            })
+            .collect();
+        self.zero_grad();
+        let n = grads.len() as f64;
+        (0..self.parameters().len())
+            .map(|i| {
+                let mean = grads.iter().map(|g| g[i]).sum::<f64>() / n;
+                grads.iter().map(|g| (g[i] - mean).powi(2)).sum::<f64>() / n
+            })
+            .collect()
+    }
+
+    /// Gradient centralization: subtracts from each weight gradient the mean weight
+    /// gradient of its neuron, in place. Bias gradients are left as they are.
+    pub fn centralize_gradients(&self) {
+        for n in self.layers.iter().flat_map(|l| l.neurons()) {
+            let weights = n.weights();
+            let mean = weights.iter().map(|w| w.grad()).sum::<f64>() / weights.len() as f64;
+            weights.iter().for_each(|w| w.set_grad(w.grad() - mean));
+        }
+    }
+
+    /// Applies `Layer::max_norm_constraint` to every layer that is not frozen.
+    pub fn apply_max_norm(&self, max_norm: f64) {
+        self.layers
+            .iter()
+            .filter(|l| !l.is_frozen())
+            .for_each(|l| l.max_norm_constraint(max_norm));
+    }
+
+    /// Number of parameters with `|data| > eps`, i.e. not (effectively) pruned.
+    pub fn active_parameter_count(&self, eps: f64) -> usize {
+        self.parameters()
+            .iter()
+            .filter(|p| p.data().abs() > eps)
+            .count()
+    }
+
+    /// Ids of the parameters of the frozen layers.
+    fn frozen_parameters(&self) -> HashSet<u64> {
+        self.layers
+            .iter()
+            .filter(|l| l.is_frozen())
+            .flat_map(|l| l.parameters())
+            .map(|p| p.id())
+            .collect()
+    }
+
+    /// Number of parameters whose loss gradient, averaged in absolute value over the
+    /// examples of `(xs, ys)`, exceeds `threshold`: a rough measure of how much of the
+    /// network the data actually trains. Frozen layers are not counted. The gradients
+    /// are zeroed afterwards.
+    pub fn effective_parameters(&self, xs: &[Vec<f64>], ys: &[f64], threshold: f64) -> usize {
+        let mut mean_abs = vec![0.0; self.parameters().len()];
+        for (x, y) in xs.iter().zip(ys) {
+            self.zero_grad();
+            self.loss_graph(std::slice::from_ref(x), &[*y]).backward();
+            mean_abs
+                .iter_mut()
+                .zip(self.save_grads())
+                .for_each(|(m, g)| *m += g.abs() / xs.len() as f64);
+        }
+        self.zero_grad();
+        let frozen = self.frozen_parameters();
+        self.parameters()
+            .iter()
+            .zip(mean_abs)
+            .filter(|(p, m)| !frozen.contains(&p.id()) && *m > threshold)
+            .count()
+    }
+
+    /// L2 norm of all parameters.
+    pub fn param_norm(&self) -> f64 {
+        self.parameters()
+            .iter()
+            .map(|p| p.data().powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Gradient and parameter diagnostics for the sum of squared errors on `(xs, ys)`.
+    /// The gradients are zeroed afterwards.
+    pub fn stability_report(&self, xs: &[Vec<f64>], ys: &[f64]) -> StabilityReport {
+        let loss = self.loss_graph(xs, ys);
+        self.zero_grad();
+        loss.backward();
+        let grad_norm = self.grad_norm();
+        self.zero_grad();
+        let param_norm = self.param_norm();
+        StabilityReport {
+            grad_norm,
+            param_norm,
+            non_finite_params: self
+                .parameters()
+                .iter()
+                .filter(|p| !p.data().is_finite())
+                .count(),
+            grad_to_param_ratio: grad_norm / param_norm,
+        }
+    }
+
+    /// One full-batch step of plain gradient descent on `loss`: forward, backward and
+    /// `update(lr)`. Returns the loss after the update, so repeated calls show the
+    /// training progress.
+    pub fn train_step(&self, xs: &[Vec<f64>], ys: &[f64], lr: f64) -> f64 {
+        let loss = self.loss_graph(xs, ys);
+        self.zero_grad();
+        loss.backward();
+        self.update(lr);
+        self.loss_graph(xs, ys).data()
+    }
+
+    pub fn train(&self, xs: Vec<Vec<f64>>, ys: Vec<f64>, config: &TrainConfig) -> Vec<EpochMetric> {
         let xs: Vec<Vec<Value>> = xs
             .into_iter()
             .map(|x| {
@@ -61,37 +709,483 @@ impl Mlp {
             })
             .collect();
 
-        for _ in 0..n {
-            // forward pass
-            let ypred: Vec<Value> = xs
-                .iter()
-                .map(|x| self.forward(x.clone())[0].clone())
-                .collect();
-            let loss: Value = ys
-                .iter()
-                .map(|y| Value::new(*y).with_label("Y"))
-                .zip(ypred)
-                .map(|(yout, ygt)| (yout - ygt).pow(&Value::new(2.0)))
-                .sum();
+        let params = self.parameters();
+        let biases: HashSet<u64> = self
+            .layers
+            .iter()
+            .flat_map(|l| l.neurons())
+            .map(|n| n.bias().id())
+            .collect();
+        let frozen = self.frozen_parameters();
+        // frozen parameters get neither gradients, penalties nor optimizer updates
+        let trainable: Vec<Value> = params
+            .iter()
+            .filter(|p| !frozen.contains(&p.id()))
+            .cloned()
+            .collect();
+        let mut optimizer = config.optimizer.clone();
+        let mut history = Vec::with_capacity(config.epochs);
+        let mut rng = rand::rng();
+        let batch_size = config.batch_size.unwrap_or(xs.len()).max(1);
+        for epoch in 0..config.epochs {
+            let order: Vec<usize> = match config.shuffle {
+                Some(seed) => shuffle_indices(xs.len(), seed, epoch),
+                None => (0..xs.len()).collect(),
+            };
+            let mut metric = EpochMetric {
+                epoch,
+                loss: 0.0,
+                lr: config.learning_rate(epoch),
+                grad_norm: 0.0,
+                val_loss: None,
+                val_accuracy: None,
+                snapshot: None,
+            };
 
-            // backward pass
-            self.zero_grad();
-            loss.backward();
+            for batch in order.chunks(batch_size) {
+                optimizer.pre_step(&trainable);
+
+                // forward pass
+                let loss: Value = batch
+                    .iter()
+                    .map(|&i| {
+                        let ypred = self.forward_dropout(xs[i].clone(), &mut rng)[0].clone();
+                        (Value::new(ys[i]).with_label("Y") - ypred).pow(&Value::new(2.0))
+                    })
+                    .sum();
 
-            // update
-            self.update(learning_rate);
+                // backward pass
+                self.zero_grad();
+                loss.backward();
+                params
+                    .iter()
+                    .filter(|p| frozen.contains(&p.id()))
+                    .for_each(|p| p.zero_grad());
+                for p in &trainable {
+                    if config.exclude_biases && biases.contains(&p.id()) {
+                        continue;
+                    }
+                    let l1 = config.l1.map_or(0.0, |l1| l1 * p.data().signum());
+                    let l2 = config.l2.map_or(0.0, |l2| l2 * p.data());
+                    p.set_grad(p.grad() + l1 + l2);
+                }
+                if config.centralize_gradients {
+                    self.centralize_gradients();
+                }
+                metric.loss += loss.data();
+                metric.grad_norm = self.grad_norm();
 
-            println!("loss: {}", loss.data());
+                if let Some(clip) = config.clip_value {
+                    clip_grad_value(&params, clip);
+                }
+
+                // update
+                optimizer.step(&trainable, metric.lr);
+                if let Some(max_norm) = config.max_norm {
+                    self.apply_max_norm(max_norm);
+                }
+            }
+            // evaluated only now: with Nesterov momentum the parameters sit at the
+            // look-ahead point between `pre_step` and `step`
+            if let Some((xs, ys)) = &config.eval {
+                metric.val_loss = Some(self.loss(xs, ys));
+                metric.val_accuracy = Some(self.accuracy(xs, ys));
+            }
+            if config.snapshots {
+                metric.snapshot = Some(params.iter().map(|p| p.data()).collect());
+            }
+
+            println!("loss: {}", metric.loss);
+            history.push(metric);
+        }
+        history
+    }
+
+    /// Simulated quantization: rounds every parameter to one of `2^bits` evenly
+    /// spaced levels between the smallest and the largest parameter, in place.
+    pub fn quantize(&self, bits: u8) {
+        assert!(bits > 0, "quantization needs at least one bit");
+        let params = self.parameters();
+        let data: Vec<f64> = params.iter().map(|p| p.data()).collect();
+        let min = data.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = data.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let step = (max - min) / (2f64.powi(bits as i32) - 1.0);
+
+        let mut squared_error = 0.0;
+        for (p, d) in params.iter().zip(data) {
+            let q = if step > 0.0 {
+                min + ((d - min) / step).round() * step
+            } else {
+                d
+            };
+            squared_error += (q - d).powi(2);
+            p.set_data(q);
         }
+        self.quantization_error
+            .set((squared_error / params.len() as f64).sqrt());
+    }
+
+    /// Root mean square change of the parameters introduced by the last `quantize`.
+    pub fn quantization_error(&self) -> f64 {
+        self.quantization_error.get()
     }
 
     pub fn forward(&self, mut x: Vec<Value>) -> Vec<Value> {
-        for layer in &self.layers {
+        let start = nodes_created();
+        for (i, layer) in self.layers.iter().enumerate() {
+            x = layer.forward(&x);
+            if let Some(max_nodes) = self.max_nodes {
+                let created = nodes_created() - start;
+                assert!(
+                    created <= max_nodes,
+                    "graph node limit exceeded: forward created {} nodes by layer {}, limit is {}",
+                    created,
+                    i,
+                    max_nodes
+                );
+            }
+        }
+        x
+    }
+
+    /// `forward` that reports the first layer with an activation above the limit set
+    /// by `with_activation_limit` (non-finite activations always exceed it).
+    pub fn forward_checked(&self, mut x: Vec<Value>) -> Result<Vec<Value>> {
+        for (i, layer) in self.layers.iter().enumerate() {
             x = layer.forward(&x);
+            if let Some(limit) = self.activation_limit {
+                if let Some(a) = x
+                    .iter()
+                    .find(|a| a.data().abs() > limit || a.data().is_nan())
+                {
+                    return Err(Error::Activation {
+                        layer: i,
+                        value: a.data(),
+                        limit,
+                    });
+                }
+            }
+        }
+        Ok(x)
+    }
+
+    /// `forward` with every output clamped to [lo, hi]. Clamped outputs pass no
+    /// gradient back, so the model is not pushed further out of range.
+    pub fn forward_clamped(&self, x: Vec<Value>, lo: f64, hi: f64) -> Vec<Value> {
+        self.forward(x).iter().map(|y| y.clamp(lo, hi)).collect()
+    }
+
+    /// Like `forward`, but returns the activations of every layer, the last one being
+    /// the network output.
+    pub fn forward_verbose(&self, x: Vec<Value>) -> Vec<Vec<Value>> {
+        let mut activations: Vec<Vec<Value>> = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            let input = activations.last().unwrap_or(&x);
+            activations.push(layer.forward(input));
+        }
+        activations
+    }
+
+    /// Mean and standard deviation of the activations of every layer over `xs`.
+    pub fn activation_stats(&self, xs: &[Vec<f64>]) -> Vec<(f64, f64)> {
+        let mut per_layer: Vec<Vec<f64>> = vec![vec![]; self.layers.len()];
+        for x in xs {
+            let activations = self.forward_verbose(x.iter().map(|e| Value::new(*e)).collect());
+            for (values, layer) in per_layer.iter_mut().zip(activations) {
+                values.extend(layer.iter().map(|a| a.data()));
+            }
+        }
+        per_layer
+            .iter()
+            .map(|values| {
+                let n = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / n;
+                let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+                (mean, var.sqrt())
+            })
+            .collect()
+    }
+
+    /// Finds an input that maximizes the activation of `neuron` in `layer` by gradient
+    /// ascent on the input, starting from `init`. The parameters are left unchanged.
+    pub fn maximize_neuron(
+        &self,
+        layer: usize,
+        neuron: usize,
+        init: Vec<f64>,
+        steps: usize,
+        learning_rate: f64,
+    ) -> Vec<f64> {
+        let inputs: Vec<Value> = init.into_iter().map(Value::new).collect();
+        for _ in 0..steps {
+            inputs.iter().for_each(|i| i.zero_grad());
+            let activations = self.forward_verbose(inputs.clone());
+            activations[layer][neuron].backward();
+            inputs.iter().for_each(|i| i.update(-learning_rate));
+        }
+        self.zero_grad();
+        inputs.iter().map(|i| i.data()).collect()
+    }
+
+    /// Inference only: computes the outputs on plain numbers without creating any
+    /// `Value` nodes, which is much cheaper than `predict` when no gradients are needed.
+    pub fn forward_eval(&self, mut x: Vec<f64>) -> Vec<f64> {
+        for layer in &self.layers {
+            x = layer.forward_eval(&x);
         }
         x
     }
 
+    pub fn predict(&self, x: Vec<f64>) -> Vec<f64> {
+        self.forward(x.into_iter().map(Value::new).collect())
+            .iter()
+            .map(|y| y.data())
+            .collect()
+    }
+
+    pub fn predict_batch(&self, xs: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        xs.iter().map(|x| self.predict(x.clone())).collect()
+    }
+
+    /// Sum of squared errors of the first output over `xs`, the loss `train` minimizes.
+    pub fn loss(&self, xs: &[Vec<f64>], ys: &[f64]) -> f64 {
+        xs.iter()
+            .zip(ys)
+            .map(|(x, y)| (self.forward_eval(x.clone())[0] - y).powi(2))
+            .sum()
+    }
+
+    /// Flatness proxy around the current parameters: the largest increase of `loss` over
+    /// `samples` random perturbations of the whole parameter vector with L2 norm
+    /// `epsilon`, or 0 if none increased it. Sharp minima score higher than flat ones.
+    /// The parameters are restored afterwards.
+    pub fn sharpness(&self, xs: &[Vec<f64>], ys: &[f64], epsilon: f64, samples: usize) -> f64 {
+        self.sharpness_seeded(xs, ys, epsilon, samples, rand::rng().random())
+    }
+
+    /// `sharpness` with reproducible perturbations.
+    pub fn sharpness_seeded(
+        &self,
+        xs: &[Vec<f64>],
+        ys: &[f64],
+        epsilon: f64,
+        samples: usize,
+        seed: u64,
+    ) -> f64 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let params = self.parameters();
+        let data: Vec<f64> = params.iter().map(|p| p.data()).collect();
+        let base = self.loss(xs, ys);
+        let mut sharpness: f64 = 0.0;
+        for _ in 0..samples {
+            let direction: Vec<f64> = (0..params.len())
+                .map(|_| rng.random_range(-1.0..1.0))
+                .collect();
+            let norm = direction.iter().map(|d| d * d).sum::<f64>().sqrt();
+            if norm == 0.0 {
+                continue;
+            }
+            for ((p, d), v) in params.iter().zip(&data).zip(&direction) {
+                p.set_data(d + epsilon * v / norm);
+            }
+            sharpness = sharpness.max(self.loss(xs, ys) - base);
+        }
+        for (p, d) in params.iter().zip(data) {
+            p.set_data(d);
+        }
+        sharpness
+    }
+
+    /// Mean of `loss(output, y)` over `(xs, ys)` for the first output, without touching
+    /// the parameters or gradients. Checking it before training catches a bad
+    /// initialization early.
+    pub fn initial_loss(
+        &self,
+        xs: &[Vec<f64>],
+        ys: &[f64],
+        loss: impl Fn(&Value, f64) -> Value,
+    ) -> f64 {
+        let total: f64 = xs
+            .iter()
+            .zip(ys)
+            .map(|(x, y)| {
+                let pred = Value::new(self.forward_eval(x.clone())[0]);
+                loss(&pred, *y).data()
+            })
+            .sum();
+        total / xs.len() as f64
+    }
+
+    /// Fraction of samples whose first output has the same sign as the target.
+    pub fn accuracy(&self, xs: &[Vec<f64>], ys: &[f64]) -> f64 {
+        if xs.is_empty() {
+            return 0.0;
+        }
+        let correct = xs
+            .iter()
+            .zip(ys)
+            .filter(|(x, y)| (self.forward_eval(x.to_vec())[0] >= 0.0) == (**y >= 0.0))
+            .count();
+        correct as f64 / xs.len() as f64
+    }
+
+    /// Times a forward pass on `x` and a backward pass from the sum of the outputs.
+    pub fn profile(&self, x: Vec<f64>) -> ProfileReport {
+        let start = Instant::now();
+        let root: Value = self
+            .forward(x.into_iter().map(Value::new).collect())
+            .into_iter()
+            .sum();
+        let forward = start.elapsed();
+        let start = Instant::now();
+        root.backward();
+        let backward = start.elapsed();
+        self.zero_grad();
+        ProfileReport {
+            forward,
+            backward,
+            nodes: root.trace().0.len(),
+        }
+    }
+
+    /// Gradient of output `output_idx` with respect to each input feature.
+    pub fn input_gradients(&self, x: Vec<f64>, output_idx: usize) -> Vec<f64> {
+        let inputs: Vec<Value> = x.into_iter().map(Value::new).collect();
+        let outputs = self.forward(inputs.clone());
+        outputs[output_idx].backward();
+        let grads = inputs.iter().map(|i| i.grad()).collect();
+        self.zero_grad();
+        grads
+    }
+
+    /// Saliency map: `|d(output)/d(input_i)|` for every input feature.
+    pub fn saliency(&self, x: Vec<f64>, output_idx: usize) -> Vec<f64> {
+        self.input_gradients(x, output_idx)
+            .into_iter()
+            .map(f64::abs)
+            .collect()
+    }
+
+    /// Panics, naming the example and output, if any prediction on `xs` differs from
+    /// `expected` by more than `eps`. Intended for golden regression tests.
+    pub fn assert_predictions_close(&self, xs: &[Vec<f64>], expected: &[Vec<f64>], eps: f64) {
+        assert_eq!(
+            xs.len(),
+            expected.len(),
+            "got {} examples but {} expectations",
+            xs.len(),
+            expected.len()
+        );
+        for (i, (actual, expected)) in self.predict_batch(xs).iter().zip(expected).enumerate() {
+            assert_eq!(
+                actual.len(),
+                expected.len(),
+                "example {}: got {} outputs but {} expected",
+                i,
+                actual.len(),
+                expected.len()
+            );
+            for (j, (a, e)) in actual.iter().zip(expected).enumerate() {
+                assert!(
+                    (a - e).abs() <= eps,
+                    "example {}, output {}: predicted {} but expected {} (eps {})",
+                    i,
+                    j,
+                    a,
+                    e,
+                    eps
+                );
+            }
+        }
+    }
+
+    /// Monte Carlo dropout: runs the network `samples` times with dropout active and
+    /// returns the per-output mean and standard deviation as an uncertainty estimate.
+    pub fn mc_dropout_predict(&self, x: Vec<f64>, samples: usize) -> (Vec<f64>, Vec<f64>) {
+        self.mc_dropout_predict_seeded(x, samples, rand::rng().random())
+    }
+
+    /// `mc_dropout_predict` with reproducible dropout masks.
+    pub fn mc_dropout_predict_seeded(
+        &self,
+        x: Vec<f64>,
+        samples: usize,
+        seed: u64,
+    ) -> (Vec<f64>, Vec<f64>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let runs: Vec<Vec<f64>> = (0..samples)
+            .map(|_| {
+                let mut a = x.clone();
+                for (i, layer) in self.layers.iter().enumerate() {
+                    a = layer.forward_eval(&a);
+                    if i + 1 < self.layers.len() {
+                        a.iter_mut().for_each(|e| *e *= self.dropout_mask(&mut rng));
+                    }
+                }
+                a
+            })
+            .collect();
+        let n = samples as f64;
+        let outputs = runs.first().map_or(0, |r| r.len());
+        let mean: Vec<f64> = (0..outputs)
+            .map(|j| runs.iter().map(|r| r[j]).sum::<f64>() / n)
+            .collect();
+        let std = (0..outputs)
+            .map(|j| {
+                let var = runs.iter().map(|r| (r[j] - mean[j]).powi(2)).sum::<f64>() / n;
+                var.sqrt()
+            })
+            .collect();
+        (mean, std)
+    }
+
+    /// Softmax over the outputs, treating each output neuron as a class logit.
+    pub fn predict_proba(&self, x: Vec<f64>) -> Vec<f64> {
+        let logits = self.forward_eval(x);
+        let max = logits.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let exps: Vec<f64> = logits.iter().map(|l| (l - max).exp()).collect();
+        let sum: f64 = exps.iter().sum();
+        exps.iter().map(|e| e / sum).collect()
+    }
+
+    /// Indices and scores of the `k` highest outputs, best first. Returns every output
+    /// if there are fewer than `k`.
+    pub fn predict_topk(&self, x: Vec<f64>, k: usize) -> Vec<(usize, f64)> {
+        let mut scored: Vec<(usize, f64)> = self.forward_eval(x).into_iter().enumerate().collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(k);
+        scored
+    }
+
+    /// Index of the highest-scoring output neuron (first one on ties).
+    pub fn predict_class(&self, x: Vec<f64>) -> usize {
+        self.forward_eval(x)
+            .iter()
+            .enumerate()
+            .fold((0, f64::NEG_INFINITY), |best, (i, &y)| {
+                if y > best.1 {
+                    (i, y)
+                } else {
+                    best
+                }
+            })
+            .0
+    }
+
+    /// Min, max, mean and standard deviation of all outputs across `xs`.
+    pub fn output_stats(&self, xs: &[Vec<f64>]) -> OutputStats {
+        let outputs: Vec<f64> = self.predict_batch(xs).into_iter().flatten().collect();
+        let n = outputs.len() as f64;
+        let mean = outputs.iter().sum::<f64>() / n;
+        let var = outputs.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / n;
+        OutputStats {
+            min: outputs.iter().copied().fold(f64::INFINITY, f64::min),
+            max: outputs.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean,
+            std: var.sqrt(),
+        }
+    }
+
     pub fn stat(&self) -> MlpStat {
         let num_layers = self.layers.len();
         let mut num_neurons = 0;
@@ -99,12 +1193,18 @@ impl Mlp {
         let mut num_biases = 0;
 
         let mut params = 0;
+        let mut per_layer = Vec::with_capacity(num_layers);
         for layer in &self.layers {
             num_neurons += layer.len();
             let layer_params = layer.parameters();
             num_weights += layer_params.len() - layer.len();
             num_biases += layer.len();
             params += layer_params.len();
+            per_layer.push(LayerStat {
+                num_neurons: layer.len(),
+                num_weights: layer_params.len() - layer.len(),
+                num_biases: layer.len(),
+            });
         }
         MlpStat {
             num_layers,
@@ -112,13 +1212,61 @@ impl Mlp {
             num_weights,
             num_biases,
             num_parameters: params,
+            per_layer,
         }
     }
 }
 
+/// Weighted average of the `forward_eval` outputs of `models` on `x`. The weights are
+/// normalized to sum to one, so they must not sum to zero.
+pub fn ensemble_predict(models: &[&Mlp], weights: &[f64], x: Vec<f64>) -> Vec<f64> {
+    assert_eq!(models.len(), weights.len(), "every model needs a weight");
+    let total: f64 = weights.iter().sum();
+    assert!(total != 0.0, "ensemble weights sum to zero");
+    let mut out: Vec<f64> = vec![];
+    for (model, w) in models.iter().zip(weights) {
+        let y = model.forward_eval(x.clone());
+        out.resize(y.len(), 0.0);
+        out.iter_mut()
+            .zip(y)
+            .for_each(|(o, yi)| *o += w / total * yi);
+    }
+    out
+}
+
+/// `loss` on `(xs, ys)` along the straight line from the parameters of `a` to those of
+/// `b`, at `steps + 1` evenly spaced points including both endpoints. Neither model is
+/// modified; they must have identical architectures.
+pub fn loss_interpolation(a: &Mlp, b: &Mlp, xs: &[Vec<f64>], ys: &[f64], steps: usize) -> Vec<f64> {
+    assert!(
+        a.shape() == b.shape(),
+        "models must have identical architectures"
+    );
+    let (pa, pb) = (a.parameters(), b.parameters());
+    let point = a.deep_copy();
+    let params = point.parameters();
+    (0..=steps)
+        .map(|i| {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                i as f64 / steps as f64
+            };
+            for ((p, wa), wb) in params.iter().zip(&pa).zip(&pb) {
+                p.set_data((1.0 - t) * wa.data() + t * wb.data());
+            }
+            point.loss(xs, ys)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::loss::{hinge_loss, squared_error};
+    use crate::optim::{Adam, Sgd};
+    use crate::train::LrSchedule;
+    use assert_approx_eq::assert_approx_eq;
 
     #[test]
     fn test_mlp_new() {
@@ -155,7 +1303,7 @@ mod tests {
             vec![1.0, 1.0, -1.0],
         ];
         let ys = vec![1.0, -1.0, -1.0, 1.0];
-        mlp.train(xs, ys, 10, 0.01);
+        mlp.train(xs, ys, &TrainConfig::new(10, 0.01));
     }
 
     #[test]
@@ -168,20 +1316,1111 @@ mod tests {
             vec![1.0, 1.0],
         ];
         let ys = vec![0.0, 1.0, 1.0, 0.0];
-        mlp.train(xs, ys, 100, 0.1);
+        mlp.train(xs, ys, &TrainConfig::new(100, 0.1));
     }
 
-    #[test]
-    fn test_stat() {
+    fn deterministic_mlp() -> Mlp {
         let mlp = Mlp::new(3, vec![4, 4, 1], true);
-        let stat = mlp.stat();
-        assert_eq!(stat.num_layers, 3);
-        assert_eq!(stat.num_neurons, 9);
-        assert_eq!(stat.num_biases, 9);
-        // 12 weights in the first layer + 16 weights in the second + 4 weights in the third
-        assert_eq!(stat.num_weights, 32);
-        // 32 weights + 9 biases
-        assert_eq!(stat.num_parameters, 41);
+        for (i, p) in mlp.parameters().iter().enumerate() {
+            p.set_data((i as f64 * 0.7).sin() * 0.5);
+        }
+        mlp
+    }
+
+    #[test]
+    fn test_quantize() {
+        let xs = vec![
+            vec![2.0, 3.0, -1.0],
+            vec![3.0, -1.0, 0.5],
+            vec![0.5, 1.0, 1.0],
+            vec![1.0, 1.0, -1.0],
+        ];
+        let max_diff = |a: &[f64], b: &[f64]| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0, f64::max)
+        };
+
+        let mlp = deterministic_mlp();
+        assert_eq!(mlp.quantization_error(), 0.0);
+        let before: Vec<f64> = mlp.predict_batch(&xs).concat();
+        mlp.quantize(8);
+        let after: Vec<f64> = mlp.predict_batch(&xs).concat();
+        assert!(mlp.quantization_error() < 0.01);
+        assert!(max_diff(&before, &after) < 0.05);
+
+        let mlp = deterministic_mlp();
+        mlp.quantize(1);
+        let after: Vec<f64> = mlp.predict_batch(&xs).concat();
+        assert!(mlp.quantization_error() > 0.1);
+        assert!(max_diff(&before, &after) > 0.1);
+    }
+
+    #[test]
+    fn test_train_cosine_annealing() {
+        let mlp = Mlp::new(2, vec![3, 1], true);
+        let xs = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+        let ys = vec![1.0, -1.0];
+        let config =
+            TrainConfig::new(20, 0.1).with_schedule(LrSchedule::CosineAnnealing { t_max: 20 });
+        mlp.train(xs, ys, &config);
+    }
+
+    #[test]
+    fn test_train_nesterov_without_momentum() {
+        let xs = vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = vec![1.0, -1.0];
+        let plain = deterministic_mlp();
+        plain.train(xs.clone(), ys.clone(), &TrainConfig::new(5, 0.05));
+        let nesterov = deterministic_mlp();
+        nesterov.train(
+            xs,
+            ys,
+            &TrainConfig::new(5, 0.05).with_optimizer(Sgd::new(0.0).with_nesterov()),
+        );
+        assert_eq!(
+            plain
+                .parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>(),
+            nesterov
+                .parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_train_adam() {
+        let xs = vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = vec![1.0, -1.0];
+        let mlp = deterministic_mlp();
+        let before: Vec<f64> = mlp.parameters().iter().map(|p| p.data()).collect();
+        mlp.train(
+            xs.clone(),
+            ys.clone(),
+            &TrainConfig::new(1, 0.01).with_optimizer(Adam::default()),
+        );
+        // Adam's first step moves every parameter by the learning rate, whatever the
+        // size of its gradient
+        for (p, b) in mlp.parameters().iter().zip(before) {
+            assert_approx_eq!((p.data() - b).abs(), 0.01, 1e-6);
+        }
+
+        let initial = deterministic_mlp().loss(&xs, &ys);
+        let history = mlp.train(
+            xs.clone(),
+            ys.clone(),
+            &TrainConfig::new(30, 0.05).with_optimizer(Adam::default()),
+        );
+        assert!(history.last().unwrap().loss < initial);
+        assert!(mlp.loss(&xs, &ys) < 0.1 * initial);
+    }
+
+    #[test]
+    fn test_average() {
+        let data = |m: &Mlp| m.parameters().iter().map(|p| p.data()).collect::<Vec<_>>();
+        let a = deterministic_mlp();
+        let avg = Mlp::average(&[&a, &a]);
+        assert_eq!(data(&avg), data(&a));
+
+        let b = deterministic_mlp();
+        b.parameters()
+            .iter()
+            .for_each(|p| p.set_data(p.data() + 1.0));
+        let avg = Mlp::average(&[&a, &b]);
+        for (m, p) in data(&avg).iter().zip(data(&a)) {
+            assert_approx_eq!(*m, p + 0.5);
+        }
+
+        // the average does not share parameters with its inputs
+        avg.parameters()[0].set_data(100.0);
+        assert_ne!(a.parameters()[0].data(), 100.0);
+    }
+
+    #[test]
+    fn test_ensemble_predict() {
+        let a = deterministic_mlp();
+        let b = Mlp::new(3, vec![4, 4, 1], true);
+        let x = vec![0.5, -1.0, 2.0];
+        let (ya, yb) = (a.predict(x.clone())[0], b.predict(x.clone())[0]);
+
+        let mean = ensemble_predict(&[&a, &b], &[2.0, 2.0], x.clone());
+        assert_approx_eq!(mean[0], (ya + yb) / 2.0);
+        let weighted = ensemble_predict(&[&a, &b], &[3.0, 1.0], x);
+        assert_approx_eq!(weighted[0], 0.75 * ya + 0.25 * yb);
+    }
+
+    #[test]
+    #[should_panic(expected = "ensemble weights sum to zero")]
+    fn test_ensemble_predict_zero_weights() {
+        let a = deterministic_mlp();
+        let b = deterministic_mlp();
+        ensemble_predict(&[&a, &b], &[1.0, -1.0], vec![0.5, -1.0, 2.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "identical architectures")]
+    fn test_average_different_architectures() {
+        Mlp::average(&[
+            &Mlp::new(2, vec![3, 1], true),
+            &Mlp::new(2, vec![4, 1], true),
+        ]);
+    }
+
+    #[test]
+    fn test_predict() {
+        let mlp = deterministic_mlp();
+        let x = vec![0.5, -1.0, 2.0];
+        let y = mlp.forward(x.iter().map(|e| Value::new(*e)).collect());
+        assert_eq!(mlp.predict(x.clone()), vec![y[0].data()]);
+        assert_eq!(mlp.predict_batch(&[x.clone(), x]).len(), 2);
+    }
+
+    #[test]
+    fn test_forward_checked() {
+        let mlp = Mlp::from_weights(vec![
+            (vec![vec![1.0, 1.0]], vec![0.0], Activation::Linear),
+            (vec![vec![1e6]], vec![0.0], Activation::Linear),
+            (vec![vec![1.0]], vec![0.0], Activation::Tanh),
+        ])
+        .unwrap()
+        .with_activation_limit(100.0);
+        let x = vec![Value::new(1.0), Value::new(2.0)];
+        match mlp.forward_checked(x.clone()) {
+            Err(Error::Activation { layer, value, .. }) => {
+                assert_eq!(layer, 1);
+                assert_eq!(value, 3e6);
+            }
+            other => panic!("expected an activation error, got {:?}", other),
+        }
+        // unlimited by default
+        let unlimited = Mlp {
+            activation_limit: None,
+            ..mlp.clone()
+        };
+        let y = unlimited.forward_checked(x).unwrap();
+        assert_approx_eq!(y[0].data(), 1.0);
+    }
+
+    #[test]
+    fn test_forward_clamped() {
+        let mlp = Mlp::from_weights(vec![(
+            vec![vec![1.0], vec![-1.0]],
+            vec![0.0, 0.0],
+            Activation::Linear,
+        )])
+        .unwrap();
+        let x = vec![Value::new(5.0)];
+        let y = mlp.forward_clamped(x.clone(), -1.0, 1.0);
+        assert_eq!(y[0].data(), 1.0);
+        assert_eq!(y[1].data(), -1.0);
+        y[0].backward();
+        assert!(mlp.parameters().iter().all(|p| p.grad() == 0.0));
+
+        let x = vec![Value::new(0.5)];
+        let y = mlp.forward_clamped(x, -1.0, 1.0);
+        assert_eq!(y[0].data(), 0.5);
+        y[0].backward();
+        assert_eq!(mlp.layers[0].neurons()[0].weights()[0].grad(), 0.5);
+    }
+
+    #[test]
+    fn test_forward_verbose() {
+        let mlp = deterministic_mlp();
+        let x = vec![Value::new(0.5), Value::new(-1.0), Value::new(2.0)];
+        let activations = mlp.forward_verbose(x.clone());
+        assert_eq!(
+            activations.iter().map(|a| a.len()).collect::<Vec<_>>(),
+            vec![4, 4, 1]
+        );
+        assert_approx_eq!(activations[2][0].data(), mlp.forward(x)[0].data());
+    }
+
+    #[test]
+    fn test_activation_stats() {
+        let mlp = deterministic_mlp();
+        let xs = [
+            vec![2.0, 3.0, -1.0],
+            vec![3.0, -1.0, 0.5],
+            vec![0.5, 1.0, 1.0],
+        ];
+        let stats = mlp.activation_stats(&xs);
+        assert_eq!(stats.len(), 3);
+        for (mean, std) in &stats {
+            assert!(*mean > -1.0 && *mean < 1.0);
+            assert!(*std >= 0.0);
+        }
+        let outputs: Vec<f64> = xs.iter().map(|x| mlp.predict(x.clone())[0]).collect();
+        assert_approx_eq!(stats[2].0, outputs.iter().sum::<f64>() / 3.0);
+    }
+
+    #[test]
+    fn test_maximize_neuron() {
+        let mlp = Mlp::new(2, vec![1], false);
+        let weights = mlp.layers[0].neurons()[0].weights();
+        weights[0].set_data(3.0);
+        weights[1].set_data(4.0);
+
+        let x = mlp.maximize_neuron(0, 0, vec![0.0, 0.0], 10, 0.1);
+        assert_approx_eq!(x[0], 3.0);
+        assert_approx_eq!(x[1], 4.0);
+        assert_eq!(mlp.grad_norm(), 0.0);
+    }
+
+    #[test]
+    fn test_tanh_impl() {
+        let exact = deterministic_mlp();
+        let fast = exact.clone().with_tanh_impl(TanhImpl::Fast);
+        let x = vec![0.5, -1.0, 2.0];
+        let y_exact = exact.predict(x.clone())[0];
+        let y_fast = fast.predict(x.clone())[0];
+        assert_ne!(y_exact, y_fast);
+        assert_approx_eq!(y_exact, y_fast, 1e-3);
+        assert_eq!(fast.forward_eval(x.clone())[0], y_fast);
+        let y = fast.forward(x.into_iter().map(Value::new).collect());
+        assert_eq!(y[0].op().as_deref(), Some("tanh_fast"));
+    }
+
+    #[test]
+    fn test_from_weights() {
+        let mlp = Mlp::from_weights(vec![
+            (
+                vec![vec![1.0, -1.0], vec![0.5, 0.5]],
+                vec![0.0, 0.1],
+                Activation::Tanh,
+            ),
+            (vec![vec![2.0, -3.0]], vec![0.5], Activation::Linear),
+        ])
+        .unwrap();
+        let x = [0.3_f64, -0.2];
+        let h0 = (1.0 * x[0] - 1.0 * x[1]).tanh();
+        let h1 = (0.5 * x[0] + 0.5 * x[1] + 0.1_f64).tanh();
+        let expected = 2.0 * h0 - 3.0 * h1 + 0.5;
+        assert_approx_eq!(mlp.predict(x.to_vec())[0], expected);
+        assert_approx_eq!(mlp.forward_eval(x.to_vec())[0], expected);
+    }
+
+    #[test]
+    fn test_from_weights_shape_mismatch() {
+        let biases = Mlp::from_weights(vec![(vec![vec![1.0]], vec![0.0, 0.0], Activation::Tanh)]);
+        assert!(matches!(biases, Err(Error::Shape(_))));
+
+        let inputs = Mlp::from_weights(vec![
+            (vec![vec![1.0], vec![1.0]], vec![0.0, 0.0], Activation::Tanh),
+            (vec![vec![1.0, 2.0, 3.0]], vec![0.0], Activation::Linear),
+        ]);
+        assert!(matches!(inputs, Err(Error::Shape(_))));
+    }
+
+    #[test]
+    fn test_train_snapshots() {
+        let mlp = deterministic_mlp();
+        let xs = vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = vec![1.0, -1.0];
+        let history = mlp.train(
+            xs.clone(),
+            ys.clone(),
+            &TrainConfig::new(4, 0.05).with_snapshots(),
+        );
+        assert_eq!(history.len(), 4);
+        let n = mlp.parameters().len();
+        assert!(history
+            .iter()
+            .all(|m| m.snapshot.as_ref().unwrap().len() == n));
+        let last = history[3].snapshot.as_ref().unwrap();
+        assert_eq!(
+            last,
+            &mlp.parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>()
+        );
+        assert_ne!(history[0].snapshot, history[3].snapshot);
+
+        let history = mlp.train(xs, ys, &TrainConfig::new(1, 0.05));
+        assert!(history[0].snapshot.is_none());
+    }
+
+    #[test]
+    fn test_train_l1() {
+        let mlp = deterministic_mlp();
+        let total = mlp.parameters().len();
+        let before = mlp.active_parameter_count(0.1);
+        assert!(before > total / 2);
+        mlp.train(
+            vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]],
+            vec![1.0, -1.0],
+            &TrainConfig::new(100, 0.01).with_l1(5.0),
+        );
+        assert!(mlp.active_parameter_count(0.1) < before);
+    }
+
+    #[test]
+    fn test_train_l2_excluding_biases() {
+        let mlp = Mlp::from_weights(vec![(vec![vec![1.0, -1.0]], vec![1.0], Activation::Linear)])
+            .unwrap();
+        // zero inputs and a matching target leave only the penalty's gradient
+        let config = TrainConfig::new(10, 0.1)
+            .with_l2(1.0)
+            .with_biases_excluded();
+        mlp.train(vec![vec![0.0, 0.0]], vec![1.0], &config);
+        let neuron = &mlp.layers[0].neurons()[0];
+        assert_approx_eq!(neuron.weights()[0].data(), 0.9f64.powi(10));
+        assert_approx_eq!(neuron.weights()[1].data(), -(0.9f64.powi(10)));
+        assert_eq!(neuron.bias().data(), 1.0);
+
+        mlp.train(
+            vec![vec![0.0, 0.0]],
+            vec![1.0],
+            &TrainConfig::new(1, 0.1).with_l2(1.0),
+        );
+        assert!(neuron.bias().data() < 1.0);
+    }
+
+    #[test]
+    fn test_train_step() {
+        let mlp = deterministic_mlp();
+        let xs = [
+            vec![2.0, 3.0, -1.0],
+            vec![3.0, -1.0, 0.5],
+            vec![0.5, 1.0, 1.0],
+            vec![1.0, 1.0, -1.0],
+        ];
+        let ys = [1.0, -1.0, -1.0, 1.0];
+        let initial = mlp.loss(&xs, &ys);
+        let losses: Vec<f64> = (0..20)
+            .map(|_| {
+                let loss = mlp.train_step(&xs, &ys, 0.05);
+                // the loss of the updated model
+                assert_approx_eq!(loss, mlp.loss(&xs, &ys));
+                loss
+            })
+            .collect();
+        assert!(losses[0] < initial);
+        assert!(losses[19] < losses[0]);
+        assert!(losses.windows(5).all(|w| w[4] < w[0]));
+    }
+
+    #[test]
+    fn test_train_keeps_frozen_layers() {
+        let mlp = deterministic_mlp().with_frozen_layers(&[0]);
+        let frozen: Vec<f64> = mlp.layers[0]
+            .parameters()
+            .iter()
+            .map(|p| p.data())
+            .collect();
+        let head: Vec<f64> = mlp.layers[1]
+            .parameters()
+            .iter()
+            .map(|p| p.data())
+            .collect();
+        let config = TrainConfig::new(5, 0.1)
+            .with_l1(0.5)
+            .with_l2(1.0)
+            .with_max_norm(0.1)
+            .with_optimizer(Sgd::new(0.9).with_nesterov());
+        mlp.train(
+            vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]],
+            vec![1.0, -1.0],
+            &config,
+        );
+        let after: Vec<f64> = mlp.layers[0]
+            .parameters()
+            .iter()
+            .map(|p| p.data())
+            .collect();
+        assert_eq!(after, frozen);
+        assert!(mlp.layers[0].parameters().iter().all(|p| p.grad() == 0.0));
+        let trained: Vec<f64> = mlp.layers[1]
+            .parameters()
+            .iter()
+            .map(|p| p.data())
+            .collect();
+        assert_ne!(trained, head);
+    }
+
+    #[test]
+    fn test_frozen_layers_share_parameters() {
+        let x = || vec![Value::new(2.0), Value::new(3.0), Value::new(-1.0)];
+        let nodes = |mlp: &Mlp| {
+            let before = nodes_created();
+            mlp.forward(x())[0].backward();
+            nodes_created() - before
+        };
+        let mlp = deterministic_mlp();
+        let frozen = deterministic_mlp().with_frozen_layers(&[0]);
+        // no per-forward copies of the frozen parameters
+        assert_eq!(nodes(&frozen), nodes(&mlp));
+
+        let data = |mlp: &Mlp| {
+            mlp.parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>()
+        };
+        let before = data(&frozen);
+        frozen.update(0.1);
+        let first_layer = frozen.layers[0].parameters().len();
+        assert_eq!(data(&frozen)[..first_layer], before[..first_layer]);
+        assert_ne!(data(&frozen)[first_layer..], before[first_layer..]);
+    }
+
+    #[test]
+    fn test_train_max_norm() {
+        let mlp = deterministic_mlp();
+        mlp.train(
+            vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]],
+            vec![1.0, -1.0],
+            &TrainConfig::new(3, 0.5).with_max_norm(0.1),
+        );
+        for n in mlp.layers.iter().flat_map(|l| l.neurons()) {
+            let norm = n.weights().iter().map(|w| w.data().powi(2)).sum::<f64>();
+            assert!(norm.sqrt() <= 0.1 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_load_compatible_layers() {
+        let source = Mlp::new(3, vec![4, 4, 1], true);
+        let target = Mlp::new(3, vec![4, 4, 2], true);
+        let head: Vec<f64> = target.layers[2]
+            .parameters()
+            .iter()
+            .map(|p| p.data())
+            .collect();
+
+        assert_eq!(target.load_compatible_layers(&source), vec![0, 1]);
+        for i in 0..2 {
+            let data = |m: &Mlp| {
+                m.layers[i]
+                    .parameters()
+                    .iter()
+                    .map(|p| p.data())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(data(&target), data(&source));
+        }
+        let after: Vec<f64> = target.layers[2]
+            .parameters()
+            .iter()
+            .map(|p| p.data())
+            .collect();
+        assert_eq!(after, head);
+        // the data is copied, the parameters are not shared
+        target.layers[0].parameters()[0].set_data(5.0);
+        assert_ne!(source.layers[0].parameters()[0].data(), 5.0);
+    }
+
+    #[test]
+    fn test_centralize_gradients() {
+        let mlp = deterministic_mlp();
+        mlp.loss_graph(&[vec![2.0, 3.0, -1.0]], &[1.0]).backward();
+        let biases: Vec<f64> = mlp.layers[0]
+            .neurons()
+            .iter()
+            .map(|n| n.bias().grad())
+            .collect();
+        mlp.centralize_gradients();
+        for n in mlp.layers.iter().flat_map(|l| l.neurons()) {
+            let mean = n.weights().iter().map(|w| w.grad()).sum::<f64>() / n.weights().len() as f64;
+            assert_approx_eq!(mean, 0.0, 1e-12);
+        }
+        let after: Vec<f64> = mlp.layers[0]
+            .neurons()
+            .iter()
+            .map(|n| n.bias().grad())
+            .collect();
+        assert_eq!(after, biases);
+
+        let trained = deterministic_mlp();
+        let plain = deterministic_mlp();
+        let xs = vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let config = TrainConfig::new(1, 0.1);
+        trained.train(
+            xs.clone(),
+            vec![1.0, -1.0],
+            &config.clone().with_centralized_gradients(),
+        );
+        plain.train(xs, vec![1.0, -1.0], &config);
+        assert_ne!(
+            trained.predict(vec![1.0, 1.0, 1.0]),
+            plain.predict(vec![1.0, 1.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn test_backward_subset() {
+        let mlp = deterministic_mlp();
+        let xs = [vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = [1.0, -1.0];
+        mlp.zero_grad();
+        mlp.loss_graph(&xs, &ys).backward();
+        let full = mlp.save_grads();
+
+        let params = mlp.parameters();
+        let subset = [params[4].clone(), params[1].clone()];
+        // stale gradients on the subset don't leak into the result
+        subset[0].set_grad(100.0);
+        let grads = mlp.backward_subset(&mlp.loss_graph(&xs, &ys), &subset);
+        assert_eq!(grads.len(), 2);
+        assert_approx_eq!(grads[0], full[4]);
+        assert_approx_eq!(grads[1], full[1]);
+    }
+
+    #[test]
+    fn test_grad_cosine() {
+        let mlp = deterministic_mlp();
+        let xs = [vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = [1.0, -1.0];
+        assert_approx_eq!(mlp.grad_cosine((&xs, &ys), (&xs, &ys)), 1.0);
+        assert_eq!(mlp.save_grads(), vec![0.0; mlp.parameters().len()]);
+
+        let linear =
+            Mlp::from_weights(vec![(vec![vec![0.0, 0.0]], vec![0.0], Activation::Linear)]).unwrap();
+        let xs = [vec![1.0, 2.0]];
+        let cosine = linear.grad_cosine((&xs, &[1.0]), (&xs, &[-1.0]));
+        assert!(cosine < 0.0);
+        assert_approx_eq!(cosine, -1.0);
+    }
+
+    #[test]
+    fn test_codegen() {
+        let mlp = Mlp::from_weights(vec![
+            (
+                vec![vec![1.0, 0.0], vec![0.0, 1.0]],
+                vec![0.5, 0.0],
+                Activation::Tanh,
+            ),
+            (vec![vec![2.0, -1.0]], vec![0.0], Activation::Linear),
+        ])
+        .unwrap();
+        let src = mlp.codegen();
+        assert!(src.starts_with("pub fn predict(x: &[f64]) -> Vec<f64> {"));
+        assert!(src.contains("const W0: [[f64; 2]; 2] = [[1.0, 0.0], [0.0, 1.0]];"));
+        assert!(src.contains("const B0: [f64; 2] = [0.5, 0.0];"));
+        assert!(src.contains("const W1: [[f64; 2]; 1] = [[2.0, -1.0]];"));
+        assert_eq!(src.matches("const W").count(), 2);
+        assert_eq!(src.matches("tanh()").count(), 1);
+    }
+
+    #[test]
+    fn test_sharpness() {
+        let xs = [vec![-1.0], vec![0.5], vec![2.0]];
+        let ys = [-2.0, 1.0, 4.0];
+        let fit =
+            Mlp::from_weights(vec![(vec![vec![2.0]], vec![0.0], Activation::Linear)]).unwrap();
+        let poor =
+            Mlp::from_weights(vec![(vec![vec![-1.0]], vec![0.5], Activation::Linear)]).unwrap();
+
+        let flat = fit.sharpness_seeded(&xs, &ys, 0.1, 20, 7);
+        let sharp = poor.sharpness_seeded(&xs, &ys, 0.1, 20, 7);
+        assert!(flat >= 0.0);
+        assert!(flat < sharp);
+        assert_eq!(flat, fit.sharpness_seeded(&xs, &ys, 0.1, 20, 7));
+        assert_eq!(
+            fit.parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>(),
+            vec![2.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_loss_interpolation() {
+        let xs = [vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = [1.0, -1.0];
+        let a = deterministic_mlp();
+        let b = deterministic_mlp();
+        b.parameters()
+            .iter()
+            .for_each(|p| p.set_data(p.data() * -0.5 + 0.1));
+
+        let losses = loss_interpolation(&a, &b, &xs, &ys, 4);
+        assert_eq!(losses.len(), 5);
+        assert_approx_eq!(losses[0], a.loss(&xs, &ys));
+        assert_approx_eq!(losses[4], b.loss(&xs, &ys));
+        assert_ne!(losses[2], losses[0]);
+        assert_eq!(
+            a.parameters()[0].data(),
+            deterministic_mlp().parameters()[0].data()
+        );
+    }
+
+    #[test]
+    fn test_effective_parameters() {
+        let xs = [vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = [1.0, -1.0];
+        let mlp = deterministic_mlp();
+        let all = mlp.effective_parameters(&xs, &ys, 1e-6);
+        assert!(all > 0);
+        assert!(all <= mlp.parameters().len());
+
+        let frozen = mlp.deep_copy().with_frozen_layers(&[0]);
+        let first_layer = mlp.layers[0].parameters().len();
+        let remaining = frozen.effective_parameters(&xs, &ys, 1e-6);
+        assert!(remaining < all);
+        assert!(remaining <= mlp.parameters().len() - first_layer);
+        // freezing stops the gradients but not the forward pass
+        assert_eq!(frozen.predict(xs[0].clone()), mlp.predict(xs[0].clone()));
+        assert_eq!(mlp.effective_parameters(&xs, &ys, f64::INFINITY), 0);
+    }
+
+    #[test]
+    fn test_grad_variance() {
+        let mlp = deterministic_mlp();
+        let xs = vec![vec![2.0, 3.0, -1.0]; 3];
+        let variance = mlp.grad_variance(&xs, &[1.0; 3]);
+        assert_eq!(variance.len(), mlp.parameters().len());
+        assert!(variance.iter().all(|v| v.abs() < 1e-12));
+
+        let linear =
+            Mlp::from_weights(vec![(vec![vec![0.0]], vec![0.0], Activation::Linear)]).unwrap();
+        // d(loss)/d(b) = 2 * (b - y): the gradients are -2 and 2
+        let variance = linear.grad_variance(&[vec![0.0], vec![0.0]], &[1.0, -1.0]);
+        assert_approx_eq!(variance[1], 4.0);
+        assert_eq!(linear.save_grads(), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_fisher_diagonal() {
+        let mlp =
+            Mlp::from_weights(vec![(vec![vec![0.5, 0.5]], vec![0.0], Activation::Linear)]).unwrap();
+        let xs = [vec![10.0, 0.1], vec![-10.0, 0.2]];
+        let fisher = mlp.fisher_diagonal(&xs, &[1.0, 1.0]);
+        assert_eq!(fisher.len(), 3);
+        assert!(fisher.iter().all(|f| *f >= 0.0));
+        assert!(fisher[0] > fisher[1]);
+        // d(loss)/d(w0) = 2 * (w.x + b - y) * x0
+        let expected = ((2.0 * 4.05 * 10.0_f64).powi(2) + (2.0 * -5.9 * -10.0_f64).powi(2)) / 2.0;
+        assert_approx_eq!(fisher[0], expected);
+        assert_eq!(mlp.grad_norm(), 0.0);
+    }
+
+    #[test]
+    fn test_numerical_gradients() {
+        let mlp = deterministic_mlp();
+        let xs = [
+            vec![2.0, 3.0, -1.0],
+            vec![3.0, -1.0, 0.5],
+            vec![0.5, 1.0, 1.0],
+        ];
+        let ys = [1.0, -1.0, -1.0];
+        let before: Vec<f64> = mlp.parameters().iter().map(|p| p.data()).collect();
+        let numerical = mlp.numerical_gradients(&xs, &ys, 1e-6);
+        assert_eq!(
+            mlp.parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>(),
+            before
+        );
+
+        mlp.loss_graph(&xs, &ys).backward();
+        let analytic: Vec<f64> = mlp.parameters().iter().map(|p| p.grad()).collect();
+        assert_eq!(numerical.len(), analytic.len());
+        for (n, a) in numerical.iter().zip(&analytic) {
+            assert_approx_eq!(n, a, 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_stability_report() {
+        let mlp = Mlp::new(3, vec![4, 4, 1], true);
+        let xs = [vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let report = mlp.stability_report(&xs, &[1.0, -1.0]);
+        assert!(report.grad_norm.is_finite());
+        assert!(report.param_norm.is_finite() && report.param_norm > 0.0);
+        assert!(report.grad_to_param_ratio.is_finite());
+        assert_eq!(report.non_finite_params, 0);
+        assert_approx_eq!(
+            report.grad_to_param_ratio,
+            report.grad_norm / report.param_norm
+        );
+        assert_eq!(mlp.grad_norm(), 0.0);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mlp = deterministic_mlp();
+        let data = |m: &Mlp| m.parameters().iter().map(|p| p.data()).collect::<Vec<_>>();
+        let before = data(&mlp);
+        mlp.forward(vec![Value::new(1.0), Value::new(2.0), Value::new(3.0)])[0].backward();
+
+        mlp.reset(Some(42));
+        let first = data(&mlp);
+        assert_ne!(first, before);
+        assert!(mlp.parameters().iter().all(|p| p.grad() == 0.0));
+        assert!(first.iter().all(|d| (-1.0..1.0).contains(d)));
+
+        mlp.reset(None);
+        assert_ne!(data(&mlp), first);
+        mlp.reset(Some(42));
+        assert_eq!(data(&mlp), first);
+    }
+
+    #[test]
+    fn test_initial_loss() {
+        let mlp =
+            Mlp::from_weights(vec![(vec![vec![0.0, 0.0]], vec![0.0], Activation::Linear)]).unwrap();
+        let xs = [vec![1.0, 2.0], vec![-1.0, 0.5], vec![3.0, 3.0]];
+        let ys = [1.0, -2.0, 3.0];
+        assert_approx_eq!(mlp.initial_loss(&xs, &ys, squared_error), 14.0 / 3.0);
+        assert_approx_eq!(mlp.initial_loss(&xs, &ys, hinge_loss), 1.0);
+    }
+
+    #[test]
+    fn test_train_eval() {
+        let mlp = deterministic_mlp();
+        let val_xs = vec![vec![1.0, 0.5, -1.0], vec![-1.0, -0.5, 1.0]];
+        let val_ys = vec![1.0, -1.0];
+        let config = TrainConfig::new(5, 0.05).with_eval(val_xs.clone(), val_ys.clone());
+        let history = mlp.train(
+            vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]],
+            vec![1.0, -1.0],
+            &config,
+        );
+        assert_eq!(history.len(), 5);
+        for m in &history {
+            let acc = m.val_accuracy.unwrap();
+            assert!((0.0..=1.0).contains(&acc));
+            assert!(m.val_loss.unwrap() >= 0.0);
+        }
+        assert_approx_eq!(
+            mlp.loss(&[vec![1.0, 0.5, -1.0]], &[1.0]),
+            (mlp.forward_eval(vec![1.0, 0.5, -1.0])[0] - 1.0).powi(2)
+        );
+
+        let history = mlp.train(
+            vec![vec![1.0, 0.0, 0.0]],
+            vec![1.0],
+            &TrainConfig::new(1, 0.1),
+        );
+        assert!(history[0].val_loss.is_none());
+        assert!(history[0].val_accuracy.is_none());
+    }
+
+    #[test]
+    fn test_train_shuffled_batches() {
+        let xs = vec![
+            vec![2.0, 3.0, -1.0],
+            vec![3.0, -1.0, 0.5],
+            vec![0.5, 1.0, 1.0],
+            vec![1.0, 1.0, -1.0],
+        ];
+        let ys = vec![1.0, -1.0, -1.0, 1.0];
+        let trained = |config: &TrainConfig| {
+            let mlp = deterministic_mlp();
+            mlp.train(xs.clone(), ys.clone(), config);
+            mlp.parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>()
+        };
+
+        // one batch of every sample is full-batch training
+        let full = trained(&TrainConfig::new(3, 0.05));
+        assert_eq!(trained(&TrainConfig::new(3, 0.05).with_batch_size(4)), full);
+
+        let shuffled = trained(&TrainConfig::new(3, 0.05).with_batch_size(1).with_shuffle(7));
+        assert_eq!(
+            trained(&TrainConfig::new(3, 0.05).with_batch_size(1).with_shuffle(7)),
+            shuffled
+        );
+        assert_ne!(
+            trained(&TrainConfig::new(3, 0.05).with_batch_size(1).with_shuffle(8)),
+            shuffled
+        );
+        assert_ne!(
+            trained(&TrainConfig::new(3, 0.05).with_batch_size(1)),
+            shuffled
+        );
+    }
+
+    #[test]
+    fn test_train_eval_nesterov() {
+        let xs = vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = vec![1.0, -1.0];
+        let mlp = deterministic_mlp();
+        let config = TrainConfig::new(3, 0.05)
+            .with_optimizer(Sgd::new(0.9).with_nesterov())
+            .with_eval(xs.clone(), ys.clone());
+        let history = mlp.train(xs.clone(), ys.clone(), &config);
+        // the last metrics describe the trained model, not the look-ahead point
+        let last = history.last().unwrap();
+        assert_eq!(last.val_loss, Some(mlp.loss(&xs, &ys)));
+        assert_eq!(last.val_accuracy, Some(mlp.accuracy(&xs, &ys)));
+    }
+
+    #[test]
+    fn test_parameters_order() {
+        let mlp = deterministic_mlp();
+        let expected: Vec<u64> = mlp
+            .layers
+            .iter()
+            .flat_map(|l| l.neurons())
+            .flat_map(|n| n.weights().iter().chain([n.bias()]).map(|p| p.id()))
+            .collect();
+        let ids = |m: &Mlp| m.parameters().iter().map(|p| p.id()).collect::<Vec<_>>();
+        assert_eq!(ids(&mlp), expected);
+        assert_eq!(ids(&mlp), ids(&mlp));
+    }
+
+    #[test]
+    fn test_profile() {
+        let mlp = deterministic_mlp();
+        let x = vec![0.5, -1.0, 2.0];
+        let report = mlp.profile(x.clone());
+        assert!(report.forward >= Duration::ZERO);
+        assert!(report.backward >= Duration::ZERO);
+        let root: Value = mlp
+            .forward(x.into_iter().map(Value::new).collect())
+            .into_iter()
+            .sum();
+        assert_eq!(report.nodes, root.trace().0.len());
+    }
+
+    #[test]
+    fn test_saliency() {
+        let mlp = Mlp::new(3, vec![1], false);
+        let weights = [0.5, -2.0, 0.0];
+        for (w, v) in mlp.layers[0].neurons()[0].weights().iter().zip(weights) {
+            w.set_data(v);
+        }
+        let x = vec![1.0, 2.0, 3.0];
+        assert_eq!(mlp.input_gradients(x.clone(), 0), vec![0.5, -2.0, 0.0]);
+        assert_eq!(mlp.saliency(x, 0), vec![0.5, 2.0, 0.0]);
+        assert!(mlp.parameters().iter().all(|p| p.grad() == 0.0));
+    }
+
+    #[test]
+    fn test_assert_predictions_close() {
+        let mlp = deterministic_mlp();
+        let xs = vec![vec![0.5, -1.0, 2.0], vec![1.0, 1.0, 1.0]];
+        let expected = mlp.predict_batch(&xs);
+        mlp.assert_predictions_close(&xs, &expected, 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "example 1, output 0")]
+    fn test_assert_predictions_close_mismatch() {
+        let mlp = deterministic_mlp();
+        let xs = vec![vec![0.5, -1.0, 2.0], vec![1.0, 1.0, 1.0]];
+        let mut expected = mlp.predict_batch(&xs);
+        expected[1][0] += 0.1;
+        mlp.assert_predictions_close(&xs, &expected, 1e-3);
+    }
+
+    #[test]
+    fn test_mc_dropout_predict() {
+        let mlp = deterministic_mlp();
+        let x = vec![0.5, -1.0, 2.0];
+        let (mean, std) = mlp.mc_dropout_predict(x.clone(), 10);
+        assert_approx_eq!(mean[0], mlp.predict(x.clone())[0]);
+        assert_approx_eq!(std[0], 0.0);
+
+        let dropout = mlp.clone().with_dropout(0.5);
+        let (mean, std) = dropout.mc_dropout_predict_seeded(x.clone(), 50, 3);
+        assert!(std[0] > 0.0);
+        assert_eq!(
+            dropout.mc_dropout_predict_seeded(x.clone(), 50, 3),
+            (mean, std)
+        );
+        // dropout only applies in train mode
+        assert_eq!(dropout.predict(x.clone()), mlp.predict(x));
+    }
+
+    #[test]
+    fn test_predict_topk() {
+        let mlp = Mlp::from_weights(vec![(
+            vec![vec![0.0]; 4],
+            vec![0.5, -1.0, 2.0, 0.1],
+            Activation::Linear,
+        )])
+        .unwrap();
+        assert_eq!(mlp.predict_topk(vec![1.0], 2), vec![(2, 2.0), (0, 0.5)]);
+        let all = mlp.predict_topk(vec![1.0], 10);
+        assert_eq!(
+            all.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            vec![2, 0, 3, 1]
+        );
+        assert!(mlp.predict_topk(vec![1.0], 0).is_empty());
+    }
+
+    #[test]
+    fn test_predict_class() {
+        let mlp = Mlp::new(2, vec![3], false);
+        mlp.map_parameters(|_| 0.1);
+        mlp.layers[0].neurons()[1].bias().set_data(5.0);
+        let x = vec![0.3, -0.7];
+        assert_eq!(mlp.predict_class(x.clone()), 1);
+        let proba = mlp.predict_proba(x);
+        assert_eq!(proba.len(), 3);
+        assert_approx_eq!(proba.iter().sum::<f64>(), 1.0);
+        assert!(proba[1] > 0.9);
+        assert_approx_eq!(proba[0], proba[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "graph node limit exceeded")]
+    fn test_max_nodes() {
+        let mlp = Mlp::new(10, vec![50, 50, 1], true).with_max_nodes(100);
+        mlp.forward((0..10).map(|i| Value::new(i as f64)).collect());
+    }
+
+    #[test]
+    fn test_max_nodes_not_exceeded() {
+        let mlp = Mlp::new(3, vec![4, 4, 1], true).with_max_nodes(1_000);
+        mlp.forward(vec![Value::new(0.1), Value::new(0.2), Value::new(0.3)]);
+    }
+
+    #[test]
+    fn test_forward_eval() {
+        for activation_last_layer in [true, false] {
+            let mlp = Mlp::new(3, vec![4, 4, 2], activation_last_layer);
+            for x in [vec![0.5, -1.0, 2.0], vec![3.0, 0.0, -0.1]] {
+                let expected = mlp.predict(x.clone());
+                for (y, e) in mlp.forward_eval(x).iter().zip(expected) {
+                    assert_approx_eq!(*y, e, 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_output_stats() {
+        let mlp = Mlp::new(3, vec![4, 4, 2], true);
+        let xs: Vec<Vec<f64>> = (0..20)
+            .map(|i| vec![i as f64 - 10.0, (i as f64).sin() * 5.0, 3.0])
+            .collect();
+        let stats = mlp.output_stats(&xs);
+        assert!(stats.min > -1.0 && stats.max < 1.0);
+        assert!(stats.min <= stats.mean && stats.mean <= stats.max);
+        assert!(stats.std >= 0.0);
+    }
+
+    #[test]
+    fn test_train_clip_value() {
+        let xs = vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]];
+        let ys = vec![1.0, -1.0];
+        let mlp = deterministic_mlp();
+        let before: Vec<f64> = mlp.parameters().iter().map(|p| p.data()).collect();
+        mlp.train(xs, ys, &TrainConfig::new(1, 1.0).with_clip_value(0.01));
+        for (p, b) in mlp.parameters().iter().zip(before) {
+            assert!(p.grad().abs() <= 0.01);
+            assert!((p.data() - b).abs() <= 0.01 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_load_torch_json() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/torch_mlp.json");
+        let mlp = Mlp::new(2, vec![2, 1], false);
+        mlp.load_torch_json(path).unwrap();
+
+        assert_eq!(
+            mlp.parameters()
+                .iter()
+                .map(|p| p.data())
+                .collect::<Vec<_>>(),
+            vec![0.5, -0.25, 0.1, -1.0, 0.75, -0.2, 1.5, -0.5, 0.3]
+        );
+        // reference output of the PyTorch model for the same input
+        assert_approx_eq!(mlp.predict(vec![0.5, -1.0])[0], 1.5534207874186352, 1e-12);
+
+        let mlp = Mlp::new(2, vec![3, 1], false);
+        assert!(matches!(mlp.load_torch_json(path), Err(Error::Shape(_))));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mlp = deterministic_mlp();
+        let config = TrainConfig::new(20, 0.05)
+            .with_schedule(LrSchedule::CosineAnnealing { t_max: 20 })
+            .with_optimizer(Adam::new(0.8, 0.99).with_eps(1e-6))
+            .with_batch_size(2)
+            .with_shuffle(42)
+            .with_l2(0.01);
+        mlp.train(
+            vec![vec![2.0, 3.0, -1.0], vec![3.0, -1.0, 0.5]],
+            vec![1.0, -1.0],
+            &config,
+        );
+        let training = TrainingConfig::from(&config);
+        let path = std::env::temp_dir().join("micrograd_save_load_test.json");
+        let path = path.to_str().unwrap();
+
+        mlp.save(path, Some(&training)).unwrap();
+        let (loaded, loaded_training) = Mlp::load(path).unwrap();
+        assert_eq!(loaded_training, Some(training));
+        let training = loaded_training.unwrap();
+        assert_eq!(
+            training.optimizer,
+            Adam::new(0.8, 0.99).with_eps(1e-6).into()
+        );
+        assert_eq!(training.shuffle, Some(42));
+        let x = vec![0.5, -1.0, 2.0];
+        assert_eq!(loaded.predict(x.clone()), mlp.predict(x));
+        assert_eq!(loaded.stat().to_string(), mlp.stat().to_string());
+
+        mlp.save(path, None).unwrap();
+        let (_, loaded_training) = Mlp::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded_training, None);
+    }
+
+    #[test]
+    fn test_for_each_layer() {
+        let mlp = Mlp::new(3, vec![4, 4, 1], true);
+        let mut num_neurons = 0;
+        let mut indices = vec![];
+        mlp.for_each_layer(|i, l| {
+            indices.push(i);
+            num_neurons += l.len();
+        });
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert_eq!(num_neurons, mlp.stat().num_neurons);
+    }
+
+    #[test]
+    fn test_map_parameters() {
+        let mlp = deterministic_mlp();
+        let before: Vec<f64> = mlp.parameters().iter().map(|p| p.data()).collect();
+        mlp.map_parameters(|d| d * 2.0);
+        for (p, b) in mlp.parameters().iter().zip(before) {
+            assert_eq!(p.data(), b * 2.0);
+        }
+    }
+
+    #[test]
+    fn test_stat() {
+        let mlp = Mlp::new(3, vec![4, 4, 1], true);
+        let stat = mlp.stat();
+        assert_eq!(stat.num_layers, 3);
+        assert_eq!(stat.num_neurons, 9);
+        assert_eq!(stat.num_biases, 9);
+        // 12 weights in the first layer + 16 weights in the second + 4 weights in the third
+        assert_eq!(stat.num_weights, 32);
+        // 32 weights + 9 biases
+        assert_eq!(stat.num_parameters, 41);
+        assert_eq!(
+            stat.per_layer
+                .iter()
+                .map(|l| l.num_weights)
+                .collect::<Vec<_>>(),
+            vec![12, 16, 4]
+        );
+        assert_eq!(
+            stat.per_layer
+                .iter()
+                .map(|l| l.num_neurons)
+                .collect::<Vec<_>>(),
+            vec![4, 4, 1]
+        );
 
         let mlp = Mlp::new(2, vec![3, 1], true);
         let stat = mlp.stat();
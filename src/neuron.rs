@@ -1,34 +1,132 @@
-use crate::value::Value;
+use crate::value::{fast_tanh, Value};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Nonlinearity applied to a neuron's weighted sum.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Activation {
+    Tanh,
+    Linear,
+}
+
+/// How tanh activations are computed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TanhImpl {
+    /// `f64::tanh`.
+    #[default]
+    Exact,
+    /// The rational approximation `value::fast_tanh`, within
+    /// `value::FAST_TANH_TOLERANCE` of the exact value.
+    Fast,
+}
+
+impl Activation {
+    fn apply(&self, z: Value, tanh: TanhImpl) -> Value {
+        match (self, tanh) {
+            (Activation::Tanh, TanhImpl::Exact) => z.tanh(),
+            (Activation::Tanh, TanhImpl::Fast) => z.tanh_fast(),
+            (Activation::Linear, _) => z,
+        }
+    }
+
+    fn apply_eval(&self, z: f64, tanh: TanhImpl) -> f64 {
+        match (self, tanh) {
+            (Activation::Tanh, TanhImpl::Exact) => z.tanh(),
+            (Activation::Tanh, TanhImpl::Fast) => fast_tanh(z),
+            (Activation::Linear, _) => z,
+        }
+    }
+}
+
+impl From<bool> for Activation {
+    /// `true` is the tanh nonlinearity, `false` a linear neuron.
+    fn from(nonlinear: bool) -> Self {
+        if nonlinear {
+            Activation::Tanh
+        } else {
+            Activation::Linear
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Neuron {
     weights: Vec<Value>,
     bias: Value,
-    activation: bool,
+    activation: Activation,
+    tanh: TanhImpl,
 }
 
 impl Neuron {
     pub fn new(nin: usize, activation: bool) -> Self {
         let mut rng = rand::rng();
         Self::new_internal(
-            (0..nin)
-                .map(|_| Value::new(rng.random_range(-1.0..1.0)))
-                .collect(),
-            Value::new(rng.random_range(-1.0..1.0)),
+            (0..nin).map(|_| Value::new(Self::init(&mut rng))).collect(),
+            Value::new(Self::init(&mut rng)),
+            activation.into(),
+        )
+    }
+
+    /// A neuron with the given weights and bias instead of random ones.
+    pub fn from_weights(weights: Vec<f64>, bias: f64, activation: Activation) -> Self {
+        Self::new_internal(
+            weights.into_iter().map(Value::new).collect(),
+            Value::new(bias),
             activation,
         )
     }
 
+    pub fn with_tanh_impl(mut self, tanh: TanhImpl) -> Self {
+        self.tanh = tanh;
+        self
+    }
+
+    fn init(rng: &mut impl Rng) -> f64 {
+        rng.random_range(-1.0..1.0)
+    }
+
+    /// Re-samples every parameter in place with the same scheme as `new` and zeroes
+    /// the gradients.
+    pub fn reset(&self, rng: &mut impl Rng) {
+        for p in self.parameters() {
+            p.set_data(Self::init(rng));
+            p.set_grad(0.0);
+        }
+    }
+
+    /// The weights in input order, followed by the bias.
     pub fn parameters(&self) -> Vec<Value> {
-        [&self.weights[..], &[self.bias.clone()]].concat()
+        [&self.weights[..], std::slice::from_ref(&self.bias)].concat()
+    }
+
+    pub fn weights(&self) -> &[Value] {
+        &self.weights
+    }
+
+    pub fn bias(&self) -> &Value {
+        &self.bias
+    }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
     }
 
-    fn new_internal(weights: Vec<Value>, bias: Value, activation: bool) -> Self {
+    /// A copy of the neuron backed by new parameter nodes.
+    pub fn deep_copy(&self) -> Self {
+        Self::new_internal(
+            self.weights.iter().map(|w| Value::new(w.data())).collect(),
+            Value::new(self.bias.data()),
+            self.activation,
+        )
+        .with_tanh_impl(self.tanh)
+    }
+
+    fn new_internal(weights: Vec<Value>, bias: Value, activation: Activation) -> Self {
         Self {
             weights,
             bias,
             activation,
+            tanh: TanhImpl::default(),
         }
     }
 
@@ -46,11 +144,19 @@ impl Neuron {
             .sum();
 
         let z = (v + self.bias.clone().with_label("b")).with_label("z");
-        if self.activation {
-            z.tanh().with_label("a")
-        } else {
-            z.with_label("a")
-        }
+        self.activation.apply(z, self.tanh).with_label("a")
+    }
+
+    /// Same as `forward` but on plain numbers, without building a graph.
+    pub fn forward_eval(&self, x: &[f64]) -> f64 {
+        let z = self
+            .weights
+            .iter()
+            .zip(x)
+            .map(|(wi, xi)| wi.data() * xi)
+            .sum::<f64>()
+            + self.bias.data();
+        self.activation.apply_eval(z, self.tanh)
     }
 
     pub fn zero_grad(&self) {
@@ -66,7 +172,7 @@ impl Neuron {
 
 #[cfg(test)]
 mod tests {
-    use crate::neuron::Neuron;
+    use crate::neuron::{Activation, Neuron};
     use crate::value::Value;
     use assert_approx_eq::assert_approx_eq;
 
@@ -87,7 +193,7 @@ mod tests {
         let neuron = Neuron::new_internal(
             vec![Value::new(0.2), Value::new(-0.5)],
             Value::new(0.1),
-            true,
+            Activation::Tanh,
         );
 
         assert_eq!(
@@ -105,7 +211,7 @@ mod tests {
         let neuron = Neuron::new_internal(
             vec![Value::new(0.2), Value::new(-0.5)],
             Value::new(0.1),
-            true,
+            Activation::Tanh,
         );
         let x = vec![Value::new(0.3), Value::new(0.7)]; // Input values matching the mock!
         let expected_output = (0.2f64 * 0.3f64 + (-0.5f64) * 0.7f64 + 0.1f64).tanh();
@@ -113,5 +219,6 @@ mod tests {
         let output = neuron.forward(&x);
 
         assert_approx_eq!(output.data(), expected_output, 1e-6);
+        assert_approx_eq!(neuron.forward_eval(&[0.3, 0.7]), expected_output, 1e-6);
     }
 }
@@ -0,0 +1,167 @@
+pub use crate::value::mean;
+use crate::value::Value;
+
+const NORM_EPS: f64 = 1e-5;
+
+/// Divisor used when computing a variance over N values.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VarianceMode {
+    /// Divide by N, the biased estimate normalization layers usually use.
+    #[default]
+    Population,
+    /// Divide by N - 1 (Bessel's correction), the unbiased sample estimate.
+    Sample,
+}
+
+impl VarianceMode {
+    fn divisor(&self, n: usize) -> f64 {
+        match self {
+            VarianceMode::Population => n as f64,
+            VarianceMode::Sample => n.saturating_sub(1).max(1) as f64,
+        }
+    }
+}
+
+/// Differentiable variance of `xs` with the divisor chosen by `mode`.
+pub fn variance(xs: &[Value], mode: VarianceMode) -> Value {
+    let mean = mean(xs);
+    let squares: Value = xs
+        .iter()
+        .map(|x| (x.clone() - mean.clone()).pow(&Value::new(2.0)))
+        .sum();
+    squares * Value::new(1.0 / mode.divisor(xs.len()))
+}
+
+/// (x - mean) / sqrt(var + eps) for every x in `xs`.
+fn standardize(xs: &[Value], eps: f64, mode: VarianceMode) -> Vec<Value> {
+    let mean = mean(xs);
+    let inv_std = (variance(xs, mode) + Value::new(eps)).pow(&Value::new(-0.5));
+    xs.iter()
+        .map(|x| (x.clone() - mean.clone()) * inv_std.clone())
+        .collect()
+}
+
+/// Normalizes each sample over its features.
+pub struct LayerNorm {
+    gamma: Vec<Value>,
+    beta: Vec<Value>,
+    eps: f64,
+    mode: VarianceMode,
+}
+
+impl LayerNorm {
+    pub fn new(dim: usize) -> Self {
+        Self {
+            gamma: (0..dim).map(|_| Value::new(1.0)).collect(),
+            beta: (0..dim).map(|_| Value::new(0.0)).collect(),
+            eps: NORM_EPS,
+            mode: VarianceMode::default(),
+        }
+    }
+
+    pub fn with_variance_mode(mut self, mode: VarianceMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn parameters(&self) -> Vec<Value> {
+        [&self.gamma[..], &self.beta[..]].concat()
+    }
+
+    pub fn forward(&self, x: &[Value]) -> Vec<Value> {
+        assert_eq!(x.len(), self.gamma.len(), "input has the wrong dimension");
+        standardize(x, self.eps, self.mode)
+            .into_iter()
+            .zip(self.gamma.iter().zip(&self.beta))
+            .map(|(xi, (g, b))| xi * g.clone() + b.clone())
+            .collect()
+    }
+}
+
+/// Normalizes each feature over the batch using the statistics of the current batch.
+pub struct BatchNorm1d {
+    gamma: Vec<Value>,
+    beta: Vec<Value>,
+    eps: f64,
+    mode: VarianceMode,
+}
+
+impl BatchNorm1d {
+    pub fn new(features: usize) -> Self {
+        Self {
+            gamma: (0..features).map(|_| Value::new(1.0)).collect(),
+            beta: (0..features).map(|_| Value::new(0.0)).collect(),
+            eps: NORM_EPS,
+            mode: VarianceMode::default(),
+        }
+    }
+
+    pub fn with_variance_mode(mut self, mode: VarianceMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn parameters(&self) -> Vec<Value> {
+        [&self.gamma[..], &self.beta[..]].concat()
+    }
+
+    /// `batch` holds one row of features per sample.
+    pub fn forward(&self, batch: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        let mut out = vec![Vec::with_capacity(self.gamma.len()); batch.len()];
+        for (j, (g, b)) in self.gamma.iter().zip(&self.beta).enumerate() {
+            let column: Vec<Value> = batch.iter().map(|row| row[j].clone()).collect();
+            for (row, y) in out
+                .iter_mut()
+                .zip(standardize(&column, self.eps, self.mode))
+            {
+                row.push(y * g.clone() + b.clone());
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn values(data: &[f64]) -> Vec<Value> {
+        data.iter().map(|d| Value::new(*d)).collect()
+    }
+
+    #[test]
+    fn variance_modes() {
+        // mean 2.5, sum of squared deviations 5
+        let xs = values(&[1.0, 2.0, 3.0, 4.0]);
+        assert_approx_eq!(variance(&xs, VarianceMode::Population).data(), 1.25);
+        assert_approx_eq!(variance(&xs, VarianceMode::Sample).data(), 5.0 / 3.0);
+    }
+
+    #[test]
+    fn layer_norm() {
+        let x = values(&[1.0, 2.0, 3.0, 4.0]);
+        let population = LayerNorm::new(4).forward(&x);
+        let sample = LayerNorm::new(4)
+            .with_variance_mode(VarianceMode::Sample)
+            .forward(&x);
+        assert_approx_eq!(population[0].data(), -1.5 / (1.25 + NORM_EPS).sqrt());
+        assert_approx_eq!(sample[0].data(), -1.5 / (5.0 / 3.0 + NORM_EPS).sqrt());
+    }
+
+    #[test]
+    fn batch_norm() {
+        let batch = vec![values(&[1.0, 10.0]), values(&[3.0, 10.0])];
+        let bn = BatchNorm1d::new(2).with_variance_mode(VarianceMode::Sample);
+        let out = bn.forward(&batch);
+        // feature 0: mean 2, sample variance 2
+        assert_approx_eq!(out[0][0].data(), -1.0 / (2.0 + NORM_EPS).sqrt());
+        assert_approx_eq!(out[1][0].data(), 1.0 / (2.0 + NORM_EPS).sqrt());
+        // a constant feature normalizes to beta
+        assert_approx_eq!(out[0][1].data(), 0.0);
+
+        out[0][0].backward();
+        assert!(bn.parameters().iter().all(|p| p.grad().is_finite()));
+        assert_approx_eq!(bn.parameters()[0].grad(), out[0][0].data());
+    }
+}
@@ -0,0 +1,237 @@
+use crate::value::Value;
+use std::ops::{Add, Mul, Sub};
+
+/// Step used by `complex_step`. Unlike finite differences there is no subtraction of
+/// nearly equal numbers, so the step can be tiny without losing precision.
+pub const COMPLEX_STEP: f64 = 1e-20;
+
+/// Perturbation used by `jvp` along the tangent.
+pub const JVP_STEP: f64 = 1e-6;
+
+/// Complex number used to evaluate functions along the imaginary axis.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// e^(a + ib) = e^a * (cos(b) + i * sin(b))
+    pub fn exp(self) -> Self {
+        let r = self.re.exp();
+        Self::new(r * self.im.cos(), r * self.im.sin())
+    }
+
+    /// tanh(a + ib) = (sinh(2a) + i * sin(2b)) / (cosh(2a) + cos(2b))
+    pub fn tanh(self) -> Self {
+        let d = (2.0 * self.re).cosh() + (2.0 * self.im).cos();
+        Self::new((2.0 * self.re).sinh() / d, (2.0 * self.im).sin() / d)
+    }
+}
+
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Self::new(re, 0.0)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+/// Derivative of `f` at `x` by the complex-step method: f'(x) = Im(f(x + ih)) / h.
+///
+/// `f` must be real analytic and written only in terms of the `Complex` ops. The result
+/// is accurate to machine precision, which makes it an oracle for the analytic
+/// gradients computed by `Value::backward`.
+pub fn complex_step(f: impl Fn(Complex) -> Complex, x: f64) -> f64 {
+    f(Complex::new(x, COMPLEX_STEP)).im / COMPLEX_STEP
+}
+
+/// Hessian-vector product H * `vector` of the loss built by `loss_builder` with
+/// respect to `params`, without materializing the Hessian.
+///
+/// Double backward: `Value::grad_graph` gives the gradient g as a graph, and the
+/// gradient of the dot product g · `vector` is Hv. The second pass accumulates into the
+/// grads of `params`, which are saved beforehand and restored before returning.
+pub fn hvp(loss_builder: impl Fn() -> Value, params: &[Value], vector: &[f64]) -> Vec<f64> {
+    assert_eq!(params.len(), vector.len(), "one direction per parameter");
+    let saved: Vec<f64> = params.iter().map(|p| p.grad()).collect();
+    params.iter().for_each(|p| p.zero_grad());
+
+    let dot: Value = loss_builder()
+        .grad_graph(params)
+        .into_iter()
+        .zip(vector)
+        .map(|(g, v)| g * Value::new(*v))
+        .sum();
+    dot.backward();
+
+    let hv = params.iter().map(|p| p.grad()).collect();
+    for (p, g) in params.iter().zip(saved) {
+        p.set_grad(g);
+    }
+    hv
+}
+
+/// Jacobian-vector product J * `tangent` of the outputs built by `output_builder` with
+/// respect to `inputs`: the directional derivative of every output along `tangent`.
+///
+/// This is an approximation, the central difference
+/// (f(x + h * t) - f(x - h * t)) / 2h of two forward passes, standing in for true
+/// forward-mode differentiation. It is exact (up to rounding) for linear maps.
+/// `inputs` are restored before returning.
+pub fn jvp(output_builder: impl Fn() -> Vec<Value>, inputs: &[Value], tangent: &[f64]) -> Vec<f64> {
+    assert_eq!(
+        inputs.len(),
+        tangent.len(),
+        "one tangent component per input"
+    );
+    let data: Vec<f64> = inputs.iter().map(|x| x.data()).collect();
+    let outputs_at = |h: f64| {
+        for ((x, d), t) in inputs.iter().zip(&data).zip(tangent) {
+            x.set_data(d + h * t);
+        }
+        output_builder()
+            .iter()
+            .map(|y| y.data())
+            .collect::<Vec<_>>()
+    };
+
+    let plus = outputs_at(JVP_STEP);
+    let minus = outputs_at(-JVP_STEP);
+    for (x, d) in inputs.iter().zip(data) {
+        x.set_data(d);
+    }
+    plus.iter()
+        .zip(minus)
+        .map(|(yp, ym)| (yp - ym) / (2.0 * JVP_STEP))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::Value;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn tanh() {
+        for x in [-3.0, -0.5, 0.0, 0.8814, 2.0] {
+            let a = Value::new(x);
+            a.tanh().backward();
+            assert_approx_eq!(complex_step(|z| z.tanh(), x), a.grad(), 1e-12);
+        }
+    }
+
+    #[test]
+    fn hvp_quadratic() {
+        // L = x0^2 + x0 * x1 + 1.5 * x1^2, H = [[2, 1], [1, 3]]
+        let params = vec![Value::new(0.7), Value::new(-1.2)];
+        let loss = || {
+            params[0].pow(&Value::new(2.0))
+                + params[0].clone() * params[1].clone()
+                + Value::new(1.5) * params[1].pow(&Value::new(2.0))
+        };
+
+        params[0].set_grad(0.25);
+
+        let hv = hvp(loss, &params, &[1.0, -2.0]);
+
+        assert_eq!(hv, vec![0.0, -5.0]);
+        assert_eq!(params[0].data(), 0.7);
+        assert_eq!(params[1].data(), -1.2);
+        assert_eq!(params[0].grad(), 0.25);
+        assert_eq!(params[1].grad(), 0.0);
+    }
+
+    #[test]
+    fn hvp_non_quadratic() {
+        // L = e^x * tanh(y) + x^3 * y:
+        // H = [[e^x * tanh(y) + 6xy, e^x * (1 - tanh(y)^2) + 3x^2],
+        //      [e^x * (1 - tanh(y)^2) + 3x^2, -2 * e^x * tanh(y) * (1 - tanh(y)^2)]]
+        let (x, y) = (0.3, -0.8);
+        let params = vec![Value::new(x), Value::new(y)];
+        let loss = || {
+            params[0].exp() * params[1].tanh() + params[0].pow(&Value::new(3.0)) * params[1].clone()
+        };
+        let (e, t) = (f64::exp(x), f64::tanh(y));
+        let h = [
+            [e * t + 6.0 * x * y, e * (1.0 - t * t) + 3.0 * x * x],
+            [
+                e * (1.0 - t * t) + 3.0 * x * x,
+                -2.0 * e * t * (1.0 - t * t),
+            ],
+        ];
+        let v = [0.5, 2.0];
+
+        let hv = hvp(loss, &params, &v);
+
+        for (row, d) in h.iter().zip(hv) {
+            assert_approx_eq!(d, row[0] * v[0] + row[1] * v[1], 1e-12);
+        }
+    }
+
+    #[test]
+    fn jvp_linear() {
+        let w = [[2.0, -1.0], [0.5, 3.0], [1.0, 1.0]];
+        let inputs = vec![Value::new(0.3), Value::new(-0.8)];
+        let outputs = || {
+            w.iter()
+                .map(|row| {
+                    Value::new(row[0]) * inputs[0].clone() + Value::new(row[1]) * inputs[1].clone()
+                })
+                .collect()
+        };
+        let tangent = [1.5, -2.0];
+
+        let jv = jvp(outputs, &inputs, &tangent);
+
+        assert_eq!(jv.len(), 3);
+        for (row, d) in w.iter().zip(jv) {
+            assert_approx_eq!(d, row[0] * tangent[0] + row[1] * tangent[1], 1e-8);
+        }
+        assert_eq!(inputs[0].data(), 0.3);
+        assert_eq!(inputs[1].data(), -0.8);
+    }
+
+    #[test]
+    fn expression() {
+        // f(x) = 3x * tanh(x) + e^x - x
+        let f = |z: Complex| Complex::from(3.0) * z * z.tanh() + z.exp() - z;
+        for x in [-1.5, 0.3, 1.0] {
+            let a = Value::new(x);
+            let out = Value::new(3.0) * a.clone() * a.tanh() + a.exp() - a.clone();
+            out.backward();
+            assert_approx_eq!(f(Complex::from(x)).re, out.data(), 1e-12);
+            assert_approx_eq!(complex_step(f, x), a.grad(), 1e-12);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Parameter update rule used by `Mlp::train`.
+///
+/// `pre_step` runs before the forward pass of every training step and `step` runs
+/// after the backward pass, once the gradients of `params` are populated.
+pub trait Optimizer {
+    fn pre_step(&mut self, _params: &[Value]) {}
+
+    fn step(&mut self, params: &[Value], learning_rate: f64);
+}
+
+/// Stochastic gradient descent with optional (Nesterov) momentum.
+///
+/// v = momentum * v - learning_rate * grad
+/// data = data + v
+///
+/// With Nesterov momentum the gradient is taken at the look-ahead point
+/// data + momentum * v: `pre_step` moves the parameters there and `step` moves them back
+/// before applying the update.
+///
+/// Only the hyperparameters are serialized, not the velocity.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sgd {
+    momentum: f64,
+    nesterov: bool,
+    #[serde(skip)]
+    velocity: Vec<f64>,
+}
+
+impl Sgd {
+    pub fn new(momentum: f64) -> Self {
+        Self {
+            momentum,
+            nesterov: false,
+            velocity: vec![],
+        }
+    }
+
+    pub fn with_nesterov(mut self) -> Self {
+        self.nesterov = true;
+        self
+    }
+}
+
+impl Optimizer for Sgd {
+    fn pre_step(&mut self, params: &[Value]) {
+        self.velocity.resize(params.len(), 0.0);
+        if self.nesterov {
+            for (p, v) in params.iter().zip(&self.velocity) {
+                p.set_data(p.data() + self.momentum * v);
+            }
+        }
+    }
+
+    fn step(&mut self, params: &[Value], learning_rate: f64) {
+        self.velocity.resize(params.len(), 0.0);
+        for (p, v) in params.iter().zip(self.velocity.iter_mut()) {
+            if self.nesterov {
+                p.set_data(p.data() - self.momentum * *v);
+            }
+            *v = self.momentum * *v - learning_rate * p.grad();
+            p.set_data(p.data() + *v);
+        }
+    }
+}
+
+/// Adam: gradient descent scaled by running estimates of the first (m) and second (v)
+/// moments of each parameter's gradient.
+///
+/// m = beta1 * m + (1 - beta1) * grad
+/// v = beta2 * v + (1 - beta2) * grad^2
+/// data = data - learning_rate * m_hat / (sqrt(v_hat) + eps)
+///
+/// where m_hat and v_hat are m and v divided by (1 - beta^t) to correct their bias
+/// toward zero in the first steps. The moments are keyed by parameter id; like the step
+/// count they are not serialized.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Adam {
+    beta1: f64,
+    beta2: f64,
+    eps: f64,
+    #[serde(skip)]
+    t: i32,
+    #[serde(skip)]
+    moments: HashMap<u64, (f64, f64)>,
+}
+
+impl Default for Adam {
+    fn default() -> Self {
+        Self::new(0.9, 0.999)
+    }
+}
+
+impl Adam {
+    pub fn new(beta1: f64, beta2: f64) -> Self {
+        Self {
+            beta1,
+            beta2,
+            eps: 1e-8,
+            t: 0,
+            moments: HashMap::new(),
+        }
+    }
+
+    pub fn with_eps(mut self, eps: f64) -> Self {
+        self.eps = eps;
+        self
+    }
+
+    /// The step size `base_lr / (sqrt(v_hat) + eps)` that multiplies the bias-corrected
+    /// first moment of `param` in the next update. Parameters with larger historical
+    /// gradients get smaller rates.
+    pub fn effective_lr(&self, param: &Value, base_lr: f64) -> f64 {
+        let v = self.moments.get(&param.id()).map_or(0.0, |&(_, v)| v);
+        let v_hat = if self.t > 0 {
+            v / (1.0 - self.beta2.powi(self.t))
+        } else {
+            0.0
+        };
+        base_lr / (v_hat.sqrt() + self.eps)
+    }
+}
+
+impl Optimizer for Adam {
+    fn step(&mut self, params: &[Value], learning_rate: f64) {
+        self.t += 1;
+        for p in params {
+            let grad = p.grad();
+            let (m, v) = self.moments.entry(p.id()).or_insert((0.0, 0.0));
+            *m = self.beta1 * *m + (1.0 - self.beta1) * grad;
+            *v = self.beta2 * *v + (1.0 - self.beta2) * grad * grad;
+            let m_hat = *m / (1.0 - self.beta1.powi(self.t));
+            let v_hat = *v / (1.0 - self.beta2.powi(self.t));
+            p.set_data(p.data() - learning_rate * m_hat / (v_hat.sqrt() + self.eps));
+        }
+    }
+}
+
+/// The optimizers `Mlp::train` can run, selected with `TrainConfig::with_optimizer`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OptimizerKind {
+    Sgd(Sgd),
+    Adam(Adam),
+}
+
+impl Default for OptimizerKind {
+    fn default() -> Self {
+        OptimizerKind::Sgd(Sgd::default())
+    }
+}
+
+impl From<Sgd> for OptimizerKind {
+    fn from(sgd: Sgd) -> Self {
+        OptimizerKind::Sgd(sgd)
+    }
+}
+
+impl From<Adam> for OptimizerKind {
+    fn from(adam: Adam) -> Self {
+        OptimizerKind::Adam(adam)
+    }
+}
+
+impl Optimizer for OptimizerKind {
+    fn pre_step(&mut self, params: &[Value]) {
+        match self {
+            OptimizerKind::Sgd(sgd) => sgd.pre_step(params),
+            OptimizerKind::Adam(adam) => adam.pre_step(params),
+        }
+    }
+
+    fn step(&mut self, params: &[Value], learning_rate: f64) {
+        match self {
+            OptimizerKind::Sgd(sgd) => sgd.step(params, learning_rate),
+            OptimizerKind::Adam(adam) => adam.step(params, learning_rate),
+        }
+    }
+}
+
+/// Clamps the gradient of every parameter to [-clip, clip].
+pub fn clip_grad_value(params: &[Value], clip: f64) {
+    params
+        .iter()
+        .for_each(|p| p.set_grad(p.grad().clamp(-clip, clip)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn run(optimizer: &mut impl Optimizer, steps: usize) -> Vec<f64> {
+        let params = vec![Value::new(1.0), Value::new(-0.5)];
+        for _ in 0..steps {
+            optimizer.pre_step(&params);
+            let loss = params[0].pow(&Value::new(2.0))
+                + (params[1].clone() - Value::new(2.0)).pow(&Value::new(2.0));
+            params.iter().for_each(|p| p.zero_grad());
+            loss.backward();
+            optimizer.step(&params, 0.1);
+        }
+        params.iter().map(|p| p.data()).collect()
+    }
+
+    #[test]
+    fn adam() {
+        let params = run(&mut Adam::default(), 300);
+        assert_approx_eq!(params[0], 0.0, 1e-2);
+        assert_approx_eq!(params[1], 2.0, 1e-2);
+    }
+
+    #[test]
+    fn adam_effective_lr() {
+        let params = vec![Value::new(1.0), Value::new(1.0)];
+        let mut adam = Adam::default();
+        for _ in 0..3 {
+            let loss = Value::new(10.0) * params[0].clone() + Value::new(0.1) * params[1].clone();
+            params.iter().for_each(|p| p.zero_grad());
+            loss.backward();
+            adam.step(&params, 0.01);
+        }
+        // a constant gradient g gives v_hat = g^2
+        assert_approx_eq!(adam.effective_lr(&params[0], 0.01), 0.01 / (10.0 + 1e-8));
+        assert!(adam.effective_lr(&params[0], 0.01) < adam.effective_lr(&params[1], 0.01));
+        // with a constant gradient every step moves the parameter by the learning rate
+        assert_approx_eq!(params[0].data(), 1.0 - 0.03, 1e-6);
+    }
+
+    #[test]
+    fn clip_value() {
+        let params = vec![Value::new(1.0), Value::new(-2.0), Value::new(0.5)];
+        let loss = Value::new(5.0) * params[0].clone()
+            + Value::new(-3.0) * params[1].clone()
+            + Value::new(0.1) * params[2].clone();
+        loss.backward();
+
+        clip_grad_value(&params, 1.0);
+
+        assert!(params.iter().all(|p| p.grad().abs() <= 1.0));
+        assert_eq!(
+            params.iter().map(|p| p.grad()).collect::<Vec<_>>(),
+            vec![1.0, -1.0, 0.1]
+        );
+    }
+
+    #[test]
+    fn nesterov_without_momentum_is_sgd() {
+        let plain = run(&mut Sgd::default(), 5);
+        let nesterov = run(&mut Sgd::new(0.0).with_nesterov(), 5);
+        assert_eq!(plain, nesterov);
+
+        let a = Value::new(1.0);
+        for _ in 0..5 {
+            let loss = a.pow(&Value::new(2.0));
+            a.zero_grad();
+            loss.backward();
+            a.update(0.1);
+        }
+        assert_eq!(plain[0], a.data());
+    }
+
+    #[test]
+    fn nesterov() {
+        // v1 = -0.1 * 2 = -0.2, w1 = 0.8
+        // look-ahead 0.8 + 0.9 * -0.2 = 0.62, v2 = 0.9 * -0.2 - 0.1 * 1.24 = -0.304
+        let w = run(&mut Sgd::new(0.9).with_nesterov(), 2);
+        assert_approx_eq!(w[0], 0.496);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::optim::OptimizerKind;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LrSchedule {
+    /// The base learning rate for every epoch.
+    #[default]
+    Constant,
+    /// lr * 0.5 * (1 + cos(pi * epoch / t_max)), decaying from the base rate at epoch 0
+    /// to zero at `t_max` and staying there afterwards.
+    CosineAnnealing { t_max: usize },
+}
+
+impl LrSchedule {
+    pub fn learning_rate(&self, base: f64, epoch: usize) -> f64 {
+        match self {
+            LrSchedule::Constant => base,
+            LrSchedule::CosineAnnealing { t_max } => {
+                let progress = epoch.min(*t_max) as f64 / (*t_max).max(1) as f64;
+                base * 0.5 * (1.0 + (PI * progress).cos())
+            }
+        }
+    }
+}
+
+/// Training metrics recorded for one epoch by `Mlp::train`.
+#[derive(Clone, Debug)]
+pub struct EpochMetric {
+    pub epoch: usize,
+    /// Sum of the batch losses, each taken before its batch's update.
+    pub loss: f64,
+    pub lr: f64,
+    /// Gradient norm of the last batch of the epoch.
+    pub grad_norm: f64,
+    /// Loss on the validation set after this epoch's update, if `TrainConfig::eval` is
+    /// set.
+    pub val_loss: Option<f64>,
+    /// Accuracy on the validation set after this epoch's update, if `TrainConfig::eval`
+    /// is set.
+    pub val_accuracy: Option<f64>,
+    /// Parameter data after this epoch's update, in `parameters()` order, if
+    /// `TrainConfig::snapshots` is set.
+    pub snapshot: Option<Vec<f64>>,
+}
+
+/// How a model was trained, stored next to its weights by `Mlp::save`. Build it from
+/// the `TrainConfig` passed to `Mlp::train` so it records what was actually used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrainingConfig {
+    pub learning_rate: f64,
+    pub epochs: usize,
+    pub schedule: LrSchedule,
+    pub optimizer: OptimizerKind,
+    pub batch_size: Option<usize>,
+    pub shuffle: Option<u64>,
+    pub clip_value: Option<f64>,
+    pub l1: Option<f64>,
+    pub l2: Option<f64>,
+    pub max_norm: Option<f64>,
+}
+
+impl From<&TrainConfig> for TrainingConfig {
+    fn from(config: &TrainConfig) -> Self {
+        Self {
+            learning_rate: config.learning_rate,
+            epochs: config.epochs,
+            schedule: config.schedule.clone(),
+            optimizer: config.optimizer.clone(),
+            batch_size: config.batch_size,
+            shuffle: config.shuffle,
+            clip_value: config.clip_value,
+            l1: config.l1,
+            l2: config.l2,
+            max_norm: config.max_norm,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TrainConfig {
+    pub epochs: usize,
+    pub learning_rate: f64,
+    pub schedule: LrSchedule,
+    pub optimizer: OptimizerKind,
+    pub clip_value: Option<f64>,
+    /// Number of samples per update; `None` trains on the full batch.
+    pub batch_size: Option<usize>,
+    /// Seed of the per-epoch sample order from `data::shuffle_indices`; `None` visits
+    /// the samples in the given order.
+    pub shuffle: Option<u64>,
+    /// Strength of an L1 penalty `l1 * sum(|p|)` on all parameters.
+    pub l1: Option<f64>,
+    /// Strength of an L2 penalty (weight decay) `l2 / 2 * sum(p^2)` on all parameters.
+    pub l2: Option<f64>,
+    /// Leave the biases out of the L1 and L2 penalties.
+    pub exclude_biases: bool,
+    /// Cap on the L2 norm of each neuron's weights, enforced after every update.
+    pub max_norm: Option<f64>,
+    /// Apply `Mlp::centralize_gradients` before every update.
+    pub centralize_gradients: bool,
+    /// Record a copy of every parameter after each epoch. Memory grows with the
+    /// number of epochs, so this is off by default.
+    pub snapshots: bool,
+    /// Held-out `(xs, ys)` evaluated every epoch.
+    pub eval: Option<(Vec<Vec<f64>>, Vec<f64>)>,
+}
+
+impl TrainConfig {
+    pub fn new(epochs: usize, learning_rate: f64) -> Self {
+        Self {
+            epochs,
+            learning_rate,
+            schedule: LrSchedule::default(),
+            optimizer: OptimizerKind::default(),
+            clip_value: None,
+            batch_size: None,
+            shuffle: None,
+            l1: None,
+            l2: None,
+            exclude_biases: false,
+            max_norm: None,
+            centralize_gradients: false,
+            snapshots: false,
+            eval: None,
+        }
+    }
+
+    pub fn with_schedule(mut self, schedule: LrSchedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Train with `optimizer`, an `Sgd` or an `Adam`. Every `Mlp::train` call starts from
+    /// a fresh copy of it, so no optimizer state carries over between calls.
+    pub fn with_optimizer(mut self, optimizer: impl Into<OptimizerKind>) -> Self {
+        self.optimizer = optimizer.into();
+        self
+    }
+
+    /// Clamp every gradient to [-clip, clip] before the update.
+    pub fn with_clip_value(mut self, clip: f64) -> Self {
+        self.clip_value = Some(clip);
+        self
+    }
+
+    /// Update the parameters after every `batch_size` samples instead of once per epoch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batches need at least one sample");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Visit the samples in the order `data::shuffle_indices(n, seed, epoch)`, so every
+    /// epoch is cut into different but reproducible batches.
+    pub fn with_shuffle(mut self, seed: u64) -> Self {
+        self.shuffle = Some(seed);
+        self
+    }
+
+    /// Penalize the parameters with `l1 * sum(|p|)`, which drives those that matter
+    /// little to zero. Its subgradient `l1 * sign(p)` is added to the gradients; the
+    /// recorded loss does not include the penalty.
+    pub fn with_l1(mut self, l1: f64) -> Self {
+        self.l1 = Some(l1);
+        self
+    }
+
+    /// Weight decay: penalize the parameters with `l2 / 2 * sum(p^2)`, whose gradient
+    /// `l2 * p` is added to the gradients. The recorded loss does not include it.
+    pub fn with_l2(mut self, l2: f64) -> Self {
+        self.l2 = Some(l2);
+        self
+    }
+
+    /// Apply the L1 and L2 penalties to the weights only, not to the biases.
+    pub fn with_biases_excluded(mut self) -> Self {
+        self.exclude_biases = true;
+        self
+    }
+
+    /// Apply `Mlp::apply_max_norm(max_norm)` after every update.
+    pub fn with_max_norm(mut self, max_norm: f64) -> Self {
+        self.max_norm = Some(max_norm);
+        self
+    }
+
+    /// Apply `Mlp::centralize_gradients` after the penalties and before clipping.
+    pub fn with_centralized_gradients(mut self) -> Self {
+        self.centralize_gradients = true;
+        self
+    }
+
+    /// Record the parameters after every epoch in `EpochMetric::snapshot`.
+    pub fn with_snapshots(mut self) -> Self {
+        self.snapshots = true;
+        self
+    }
+
+    /// Record validation loss and accuracy on `(xs, ys)` every epoch.
+    pub fn with_eval(mut self, xs: Vec<Vec<f64>>, ys: Vec<f64>) -> Self {
+        self.eval = Some((xs, ys));
+        self
+    }
+
+    /// Learning rate to use for the given epoch.
+    pub fn learning_rate(&self, epoch: usize) -> f64 {
+        self.schedule.learning_rate(self.learning_rate, epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn constant() {
+        let config = TrainConfig::new(10, 0.1);
+        assert_eq!(config.learning_rate(0), 0.1);
+        assert_eq!(config.learning_rate(9), 0.1);
+    }
+
+    #[test]
+    fn cosine_annealing() {
+        let schedule = LrSchedule::CosineAnnealing { t_max: 10 };
+        assert_approx_eq!(schedule.learning_rate(0.1, 0), 0.1);
+        assert_approx_eq!(schedule.learning_rate(0.1, 5), 0.05);
+        assert_approx_eq!(schedule.learning_rate(0.1, 10), 0.0);
+        assert_approx_eq!(schedule.learning_rate(0.1, 15), 0.0);
+    }
+}
@@ -1,10 +1,31 @@
-use std::cell::RefCell;
-use std::collections::HashSet;
+use crate::error::Error;
+use crate::DEFAULT_EPS;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter, Result};
-use std::iter::Sum;
-use std::ops::{Add, Mul, Sub};
-use std::rc::Rc;
-use uuid::Uuid;
+use std::iter::{Product, Sum};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Inputs to `Value::exp` are clamped to [-EXP_CLAMP, EXP_CLAMP] so that the output
+/// (e^50 ~ 5e21) can never overflow to `inf`. Typical pre-activations are orders of
+/// magnitude smaller, so in practice neither data nor gradients are affected.
+pub const EXP_CLAMP: f64 = 50.0;
+
+/// Source of node ids: every `Value` gets the next number, which is unique within
+/// the process and cheaper to create, hash and compare than a random uuid.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static NODES_CREATED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Number of nodes created so far on the current thread.
+pub fn nodes_created() -> usize {
+    NODES_CREATED.with(|n| n.get())
+}
 
 #[derive(Clone)]
 pub struct Value(Rc<RefCell<InternalValue>>);
@@ -19,7 +40,7 @@ impl Value {
         grad: f64,
         prev: Vec<Value>,
         label: Option<String>,
-        op: Option<String>,
+        op: Option<Op>,
     ) -> Self {
         Self(Rc::new(RefCell::new(InternalValue::new(
             data, grad, prev, label, op,
@@ -35,11 +56,26 @@ impl Value {
         self.0.borrow_mut().grad = 0.0;
     }
 
+    pub fn set_grad(&self, grad: f64) {
+        self.0.borrow_mut().grad = grad;
+    }
+
+    pub fn set_data(&self, data: f64) {
+        self.0.borrow_mut().data = data;
+    }
+
     pub fn update(&self, learning_rate: f64) {
         let grad = self.0.borrow().grad;
         self.0.borrow_mut().data += -learning_rate * grad;
     }
 
+    /// out = self^degree, shown as "**degree" in the graph.
+    ///
+    /// self.grad = out.grad * degree * self^(degree - 1)
+    ///
+    /// The exponent is read once as a constant: it is not a child of the output and
+    /// gets no gradient, since the polynomial and root exponents this is used for are
+    /// fixed. Use `exp` and `ln` for a trainable exponent: a^b = e^(b * ln(a)).
     pub fn pow(&self, degree: &Value) -> Self {
         let degree = degree.0.borrow().data;
         let data = self.0.borrow().data.powf(degree);
@@ -50,15 +86,15 @@ impl Value {
             0.0,
             vec![Value(lhs_internal)],
             None,
-            Some(format!("**{}", degree)),
+            Some(Op::Pow(degree)),
         );
 
         let lhs_internal = Rc::clone(&self.0);
-        let out_internal = Rc::clone(&out.0);
+        let out_internal = Rc::downgrade(&out.0);
 
         let backward = move || {
             let mut lhs = lhs_internal.borrow_mut();
-            let out_grad = out_internal.borrow().grad;
+            let out_grad = grad_of(&out_internal);
             lhs.grad += degree * lhs.data.powf(degree - 1.0) * out_grad;
         };
 
@@ -72,20 +108,14 @@ impl Value {
         let data = self.0.borrow().data.tanh();
         let lhs_internal = Rc::clone(&self.0);
 
-        let out = Self::new_internal(
-            data,
-            0.0,
-            vec![Value(lhs_internal)],
-            None,
-            Some(String::from("tanh")),
-        );
+        let out = Self::new_internal(data, 0.0, vec![Value(lhs_internal)], None, Some(Op::Tanh));
 
         let lhs_internal = Rc::clone(&self.0);
-        let out_internal = Rc::clone(&out.0);
+        let out_internal = Rc::downgrade(&out.0);
 
         let backward = move || {
             let mut lhs = lhs_internal.borrow_mut();
-            let out_grad = out_internal.borrow().grad;
+            let out_grad = grad_of(&out_internal);
             lhs.grad += (1.0 - data.powf(2.0)) * out_grad;
         };
 
@@ -95,7 +125,251 @@ impl Value {
         out
     }
 
+    /// Like `tanh`, but with the cheaper `fast_tanh` approximation, which is within
+    /// `FAST_TANH_TOLERANCE` of the exact value.
+    ///
+    /// self.grad = out.grad * (1 - out^2)
+    pub fn tanh_fast(&self) -> Self {
+        let data = fast_tanh(self.0.borrow().data);
+        self.unary(data, 1.0 - data * data, Op::FastTanh)
+    }
+
+    /// out = ln(self), with self clamped to at least `DEFAULT_EPS`.
+    ///
+    /// self.grad = out.grad * 1 / self
+    pub fn ln(&self) -> Self {
+        self.ln_eps(DEFAULT_EPS)
+    }
+
+    /// Natural logarithm, computed like `ln` but shown as "log" in the graph.
+    ///
+    /// Inputs at or below zero are clamped to `DEFAULT_EPS` rather than giving -inf or
+    /// NaN: the output is ln(1e-12) ≈ -27.6 and the gradient 1e12, large but finite,
+    /// so a cross-entropy on a zero probability does not poison the parameters.
+    pub fn log(&self) -> Self {
+        let x = self.0.borrow().data.max(DEFAULT_EPS);
+        self.unary(x.ln(), 1.0 / x, Op::Log)
+    }
+
+    pub fn ln_eps(&self, eps: f64) -> Self {
+        let x = self.0.borrow().data.max(eps);
+        self.unary(x.ln(), 1.0 / x, Op::Ln(eps))
+    }
+
+    /// out = 1 / (1 + e^-self), evaluated without overflow for large |self|.
+    ///
+    /// self.grad = out.grad * out * (1 - out)
+    pub fn sigmoid(&self) -> Self {
+        let data = sigmoid(self.0.borrow().data);
+        self.unary(data, data * (1.0 - data), Op::Sigmoid)
+    }
+
+    /// out = e^self, with self clamped to [-EXP_CLAMP, EXP_CLAMP].
+    ///
+    /// self.grad = out.grad * e^self = out.grad * out
+    pub fn exp(&self) -> Self {
+        let data = self.0.borrow().data.clamp(-EXP_CLAMP, EXP_CLAMP).exp();
+        self.unary(data, data, Op::Exp)
+    }
+
+    /// out = sqrt(self), with self clamped to at least `DEFAULT_EPS`.
+    ///
+    /// self.grad = out.grad * 1 / (2 * sqrt(self))
+    pub fn sqrt(&self) -> Self {
+        self.sqrt_eps(DEFAULT_EPS)
+    }
+
+    pub fn sqrt_eps(&self, eps: f64) -> Self {
+        let data = self.0.borrow().data.max(eps).sqrt();
+        self.unary(data, 0.5 / data, Op::Sqrt(eps))
+    }
+
+    /// out = self / rhs, with the magnitude of rhs clamped to at least `eps` (keeping
+    /// its sign, zero counts as positive). `eps = 0` is plain division.
+    ///
+    /// self.grad = out.grad * 1 / rhs
+    ///
+    /// rhs.grad = out.grad * -self / rhs^2
+    ///
+    /// For a / a the two terms cancel unless the divisor was clamped.
+    pub fn div_eps(&self, rhs: &Value, eps: f64) -> Self {
+        let is_self = Rc::ptr_eq(&self.0, &rhs.0);
+
+        let data = self.0.borrow().data / clamp_magnitude(rhs.0.borrow().data, eps);
+        let lhs_internal = Rc::clone(&self.0);
+        let rhs_internal = Rc::clone(&rhs.0);
+
+        let mut prev = vec![self.clone()];
+        if !is_self {
+            prev.push(rhs.clone());
+        }
+
+        let out = Self::new_internal(data, 0.0, prev, None, Some(Op::Div(eps)));
+        let out_internal = Rc::downgrade(&out.0);
+
+        let backward = move || {
+            let out_grad = grad_of(&out_internal);
+            let mut lhs = lhs_internal.borrow_mut();
+            if is_self {
+                let d = clamp_magnitude(lhs.data, eps);
+                lhs.grad += (1.0 - lhs.data / d) / d * out_grad;
+            } else {
+                let mut rhs = rhs_internal.borrow_mut();
+                let d = clamp_magnitude(rhs.data, eps);
+                lhs.grad += out_grad / d;
+                rhs.grad += -lhs.data / d.powi(2) * out_grad;
+            }
+        };
+
+        out.0.borrow_mut().backward = Some(Rc::new(RefCell::new(backward)));
+        out
+    }
+
+    /// out = self clamped to [lo, hi].
+    ///
+    /// self.grad = out.grad inside the range and 0 where the input was clamped.
+    pub fn clamp(&self, lo: f64, hi: f64) -> Self {
+        let x = self.0.borrow().data;
+        let inside = (lo..=hi).contains(&x);
+        self.unary(
+            x.clamp(lo, hi),
+            if inside { 1.0 } else { 0.0 },
+            Op::Clamp(lo, hi),
+        )
+    }
+
+    /// out = max(0, self).
+    ///
+    /// self.grad = out.grad for a positive input and 0 otherwise.
+    pub fn relu(&self) -> Self {
+        let x = self.0.borrow().data;
+        self.unary(x.max(0.0), if x > 0.0 { 1.0 } else { 0.0 }, Op::Relu)
+    }
+
+    /// out = self for a positive input and alpha * self otherwise, so negative inputs
+    /// keep a small gradient.
+    ///
+    /// self.grad = out.grad for a positive input and alpha * out.grad otherwise.
+    pub fn leaky_relu(&self, alpha: f64) -> Self {
+        let x = self.0.borrow().data;
+        let slope = if x > 0.0 { 1.0 } else { alpha };
+        self.unary(slope * x, slope, Op::LeakyRelu(alpha))
+    }
+
+    /// Applies a user defined unary function. `backward_local` is the local
+    /// derivative d(out)/d(self) expressed in terms of the input data.
+    ///
+    /// self.grad = out.grad * backward_local(self)
+    pub fn map(&self, forward: fn(f64) -> f64, backward_local: fn(f64) -> f64) -> Self {
+        let x = self.0.borrow().data;
+        self.unary(
+            forward(x),
+            backward_local(x),
+            Op::Map(forward, backward_local),
+        )
+    }
+
+    /// out = max(self, other).
+    ///
+    /// The gradient flows only into the larger operand (into self on ties).
+    pub fn max(&self, other: &Value) -> Self {
+        let is_self = Rc::ptr_eq(&self.0, &other.0);
+        let lhs_wins = self.data() >= other.data();
+        let data = self.data().max(other.data());
+
+        let mut prev = vec![self.clone()];
+        if !is_self {
+            prev.push(other.clone());
+        }
+
+        let out = Self::new_internal(data, 0.0, prev, None, Some(Op::Max));
+        let winner_internal = Rc::clone(if lhs_wins { &self.0 } else { &other.0 });
+        let out_internal = Rc::downgrade(&out.0);
+
+        let backward = move || {
+            let out_grad = grad_of(&out_internal);
+            winner_internal.borrow_mut().grad += out_grad;
+        };
+
+        out.0.borrow_mut().backward = Some(Rc::new(RefCell::new(backward)));
+        out
+    }
+
+    /// Applies a user defined binary op. `d_lhs` and `d_rhs` are the partial
+    /// derivatives d(out)/d(self) and d(out)/d(other) in terms of the operand data.
+    /// The node is shown as `op_name` in graphs; it cannot be serialized.
+    ///
+    /// self.grad = out.grad * d_lhs(self, other)
+    /// other.grad = out.grad * d_rhs(self, other)
+    pub fn custom_binary(
+        &self,
+        other: &Value,
+        op_name: &str,
+        forward: fn(f64, f64) -> f64,
+        d_lhs: fn(f64, f64) -> f64,
+        d_rhs: fn(f64, f64) -> f64,
+    ) -> Self {
+        let is_self = Rc::ptr_eq(&self.0, &other.0);
+        let (a, b) = (self.data(), other.data());
+
+        let mut prev = vec![self.clone()];
+        if !is_self {
+            prev.push(other.clone());
+        }
+        let op = Op::Custom {
+            name: op_name.to_string(),
+            forward,
+            d_lhs,
+            d_rhs,
+        };
+        let out = Self::new_internal(forward(a, b), 0.0, prev, None, Some(op));
+
+        let lhs_internal = Rc::clone(&self.0);
+        let rhs_internal = Rc::clone(&other.0);
+        let out_internal = Rc::downgrade(&out.0);
+
+        let backward = move || {
+            let out_grad = grad_of(&out_internal);
+            lhs_internal.borrow_mut().grad += d_lhs(a, b) * out_grad;
+            rhs_internal.borrow_mut().grad += d_rhs(a, b) * out_grad;
+        };
+
+        out.0.borrow_mut().backward = Some(Rc::new(RefCell::new(backward)));
+        out
+    }
+
+    /// Creates a node with a single child whose local derivative
+    /// d(out)/d(self) = `local_grad` is known at construction time.
+    fn unary(&self, data: f64, local_grad: f64, op: Op) -> Self {
+        let lhs_internal = Rc::clone(&self.0);
+        let out = Self::new_internal(data, 0.0, vec![Value(lhs_internal)], None, Some(op));
+
+        let lhs_internal = Rc::clone(&self.0);
+        let out_internal = Rc::downgrade(&out.0);
+
+        let backward = move || {
+            let out_grad = grad_of(&out_internal);
+            lhs_internal.borrow_mut().grad += local_grad * out_grad;
+        };
+
+        out.0.borrow_mut().backward = Some(Rc::new(RefCell::new(backward)));
+        out
+    }
+
     pub fn backward(&self) {
+        self.backward_visit(|_| {});
+    }
+
+    /// Runs `backward` and records, in reverse topological order, the id and grad of
+    /// every node right after its backward step. A node's grad is final at that point,
+    /// so the sequence replays how the gradients fill in from the output to the leaves.
+    pub fn backward_trace(&self) -> Vec<(u64, f64)> {
+        let mut steps = vec![];
+        self.backward_visit(|node| steps.push((node.id(), node.grad())));
+        steps
+    }
+
+    fn backward_visit(&self, mut visit: impl FnMut(&Value)) {
         let mut topo = vec![];
         let mut visited = HashSet::new();
         build_topo(self, &mut topo, &mut visited);
@@ -105,11 +379,59 @@ impl Value {
             if let Some(backward) = &node.0.borrow().backward {
                 backward.borrow_mut()();
             }
+            visit(node);
+        }
+    }
+
+    /// Gradients of `self` with respect to `wrt` as `Value`s, built on top of this graph
+    /// instead of being accumulated into `grad`. Unlike the result of `backward` they
+    /// can be differentiated again: backpropagating through them gives second
+    /// derivatives. No grads are touched, and inputs `self` does not depend on get the
+    /// constant 0.
+    pub fn grad_graph(&self, wrt: &[Value]) -> Vec<Value> {
+        let mut topo = vec![];
+        let mut visited = HashSet::new();
+        build_topo(self, &mut topo, &mut visited);
+
+        let mut adjoints: HashMap<u64, Value> = HashMap::new();
+        adjoints.insert(self.id(), Value::new(1.0));
+        for node in topo.iter().rev() {
+            let Some(adjoint) = adjoints.get(&node.id()).cloned() else {
+                continue;
+            };
+            let internal = node.0.borrow();
+            let Some(op) = &internal.op else {
+                continue;
+            };
+            let locals = op.local_grad_values(&internal.prev, node);
+            for (arg, local) in internal.prev.iter().zip(locals) {
+                let term = local * adjoint.clone();
+                let sum = match adjoints.remove(&arg.id()) {
+                    Some(acc) => acc + term,
+                    None => term,
+                };
+                adjoints.insert(arg.id(), sum);
+            }
+        }
+        wrt.iter()
+            .map(|w| adjoints.get(&w.id()).cloned().unwrap_or_default())
+            .collect()
+    }
+
+    /// Drops the backward closure of every node in the graph, together with the operand
+    /// handles they hold, once the graph is no longer needed (e.g. after `backward`).
+    /// A released graph cannot be backpropagated.
+    pub fn release(&self) {
+        let mut topo = vec![];
+        let mut visited = HashSet::new();
+        build_topo(self, &mut topo, &mut visited);
+        for node in topo {
+            node.0.borrow_mut().backward = None;
         }
     }
 
-    pub fn uuid(&self) -> Uuid {
-        self.0.borrow().uuid
+    pub fn id(&self) -> u64 {
+        self.0.borrow().id
     }
 
     pub fn data(&self) -> f64 {
@@ -129,7 +451,195 @@ impl Value {
     }
 
     pub fn op(&self) -> Option<String> {
-        self.0.borrow().op.clone()
+        self.0.borrow().op.as_ref().map(|op| op.to_string())
+    }
+
+    /// Ids of the leaves (nodes without children) the value depends on.
+    pub fn dependencies(&self) -> Vec<u64> {
+        let mut topo = vec![];
+        let mut visited = HashSet::new();
+        build_topo(self, &mut topo, &mut visited);
+        topo.iter()
+            .filter(|v| v.0.borrow().prev.is_empty())
+            .map(|v| v.id())
+            .collect()
+    }
+
+    /// The values in `all_inputs` the graph does not depend on. They would silently get
+    /// a zero gradient, which usually means they were not wired in.
+    pub fn unused_leaves(&self, all_inputs: &[Value]) -> Vec<Value> {
+        let mut topo = vec![];
+        let mut visited = HashSet::new();
+        build_topo(self, &mut topo, &mut visited);
+        all_inputs
+            .iter()
+            .filter(|x| !visited.contains(&x.id()))
+            .cloned()
+            .collect()
+    }
+
+    /// Flattens the graph into a `CompiledFn` that evaluates it for new leaf values
+    /// without touching any `Rc`/`RefCell`. Every leaf is an input, in the order of
+    /// `dependencies()`.
+    pub fn compile(&self) -> CompiledFn {
+        let mut topo = vec![];
+        let mut visited = HashSet::new();
+        build_topo(self, &mut topo, &mut visited);
+
+        let (leaves, nodes): (Vec<_>, Vec<_>) =
+            topo.into_iter().partition(|v| v.0.borrow().op.is_none());
+        let mut slots: HashMap<u64, usize> = HashMap::with_capacity(leaves.len() + nodes.len());
+        for (i, leaf) in leaves.iter().enumerate() {
+            slots.insert(leaf.id(), i);
+        }
+        let mut instructions = Vec::with_capacity(nodes.len());
+        for (i, node) in nodes.iter().enumerate() {
+            let node = node.0.borrow();
+            let args = node.prev.iter().map(|c| slots[&c.id()]).collect();
+            instructions.push((node.op.clone().unwrap(), args));
+            slots.insert(node.id, leaves.len() + i);
+        }
+        CompiledFn {
+            leaves: leaves.iter().map(|v| v.id()).collect(),
+            instructions,
+        }
+    }
+
+    /// The reverse pass of `compile`: a closure mapping leaf values, in the order of
+    /// `dependencies()`, to the gradients of this value with respect to every leaf.
+    pub fn compile_backward(&self) -> impl Fn(&[f64]) -> Vec<f64> {
+        let compiled = self.compile();
+        move |inputs| compiled.gradient(inputs)
+    }
+
+    /// The graph below the value in Graphviz DOT format, as drawn by
+    /// `view::print_computation_graph`.
+    pub fn to_dot(&self) -> String {
+        crate::view::print_computation_graph(self, None)
+    }
+
+    /// Writes `to_dot` to `path` as plain DOT text, for rendering with an external
+    /// Graphviz pipeline or viewer.
+    pub fn save_dot(&self, path: &str) -> crate::error::Result<()> {
+        std::fs::write(path, self.to_dot())?;
+        Ok(())
+    }
+
+    /// Serializes the graph below the value. Every node is written once, children
+    /// before parents, and refers to its operands by id, so shared subexpressions
+    /// stay shared. Graphs containing `map` or `custom_binary` nodes cannot be serialized.
+    pub fn to_expr_json(&self) -> crate::error::Result<String> {
+        let mut topo = vec![];
+        let mut visited = HashSet::new();
+        build_topo(self, &mut topo, &mut visited);
+
+        let nodes = topo
+            .iter()
+            .map(|v| {
+                let v = v.0.borrow();
+                ExprNode {
+                    id: v.id,
+                    data: v.data,
+                    label: v.label.clone(),
+                    op: v.op.clone(),
+                    args: v.prev.iter().map(|c| c.id()).collect(),
+                }
+            })
+            .collect();
+        let expr = Expr {
+            root: self.id(),
+            nodes,
+        };
+        Ok(serde_json::to_string(&expr)?)
+    }
+
+    /// Rebuilds a graph written by `to_expr_json`. Leaves keep their data, every other
+    /// node is recomputed from its rebuilt operands, and all nodes get new ids.
+    pub fn from_expr_json(json: &str) -> crate::error::Result<Value> {
+        let expr: Expr = serde_json::from_str(json)?;
+        let mut built: HashMap<u64, Value> = HashMap::with_capacity(expr.nodes.len());
+        for node in expr.nodes {
+            let args = node
+                .args
+                .iter()
+                .map(|id| {
+                    built.get(id).cloned().ok_or_else(|| {
+                        Error::Graph(format!("node {} refers to unknown node {}", node.id, id))
+                    })
+                })
+                .collect::<crate::error::Result<Vec<_>>>()?;
+            let value = match (&node.op, &args[..]) {
+                (None, []) => Value::new(node.data),
+                (Some(Op::Add), [a]) => a.clone() + a.clone(),
+                (Some(Op::Add), [a, b]) => a.clone() + b.clone(),
+                (Some(Op::Sub), [a]) => a.clone() - a.clone(),
+                (Some(Op::Sub), [a, b]) => a.clone() - b.clone(),
+                (Some(Op::Mul), [a]) => a.clone() * a.clone(),
+                (Some(Op::Mul), [a, b]) => a.clone() * b.clone(),
+                (Some(Op::Div(eps)), [a]) => a.div_eps(a, *eps),
+                (Some(Op::Div(eps)), [a, b]) => a.div_eps(b, *eps),
+                (Some(Op::Neg), [a]) => -a.clone(),
+                (Some(Op::Max), [a]) => a.max(a),
+                (Some(Op::Max), [a, b]) => a.max(b),
+                (Some(Op::Pow(degree)), [a]) => a.pow(&Value::new(*degree)),
+                (Some(Op::Tanh), [a]) => a.tanh(),
+                (Some(Op::FastTanh), [a]) => a.tanh_fast(),
+                (Some(Op::Sigmoid), [a]) => a.sigmoid(),
+                (Some(Op::Exp), [a]) => a.exp(),
+                (Some(Op::Ln(eps)), [a]) => a.ln_eps(*eps),
+                (Some(Op::Log), [a]) => a.log(),
+                (Some(Op::Sqrt(eps)), [a]) => a.sqrt_eps(*eps),
+                (Some(Op::Clamp(lo, hi)), [a]) => a.clamp(*lo, *hi),
+                (Some(Op::Relu), [a]) => a.relu(),
+                (Some(Op::LeakyRelu(alpha)), [a]) => a.leaky_relu(*alpha),
+                _ => {
+                    return Err(Error::Graph(format!(
+                        "node {} has the wrong number of operands",
+                        node.id
+                    )))
+                }
+            };
+            let value = match node.label {
+                Some(label) => value.with_label(&label),
+                None => value,
+            };
+            built.insert(node.id, value);
+        }
+        built
+            .remove(&expr.root)
+            .ok_or_else(|| Error::Graph(format!("missing root node {}", expr.root)))
+    }
+
+    /// Ids of the nodes whose |grad| is below `threshold` after `backward`, which
+    /// usually points at saturated activations blocking the gradient flow.
+    pub fn vanishing_nodes(&self, threshold: f64) -> Vec<u64> {
+        let (nodes, _) = self.trace();
+        nodes
+            .iter()
+            .filter(|n| n.grad.abs() < threshold)
+            .map(|n| n.id)
+            .collect()
+    }
+
+    /// The graph as adjacency lists: every node once, in the same order as `trace`, and
+    /// for each node the indices of its operands (children) in that list.
+    pub fn adjacency(&self) -> (Vec<DataValue>, Vec<Vec<usize>>) {
+        let mut values = vec![];
+        let mut index = HashMap::new();
+        let mut stack = vec![self.clone()];
+        while let Some(v) = stack.pop() {
+            if index.contains_key(&v.id()) {
+                continue;
+            }
+            index.insert(v.id(), values.len());
+            stack.extend(v.0.borrow().prev.iter().rev().cloned());
+            values.push(v);
+        }
+        let children = values
+            .iter()
+            .map(|v| v.0.borrow().prev.iter().map(|c| index[&c.id()]).collect())
+            .collect();
+        (values.iter().map(DataValue::from).collect(), children)
     }
 
     /// Build a set of all nodes and edges in a graph.
@@ -141,11 +651,11 @@ impl Value {
             v: &Value,
             nodes: &mut Vec<RcDataValue>,
             edges: &mut Vec<(RcDataValue, RcDataValue)>,
-            visited: &mut HashSet<Uuid>,
+            visited: &mut HashSet<u64>,
         ) {
             let data_val_ref = Rc::new(DataValue::from(v));
-            if !visited.contains(&data_val_ref.uuid) {
-                visited.insert(data_val_ref.uuid);
+            if !visited.contains(&data_val_ref.id) {
+                visited.insert(data_val_ref.id);
                 nodes.push(Rc::clone(&data_val_ref));
                 for child in &v.0.borrow().prev {
                     let child_data_val_ref = Rc::new(DataValue::from(child));
@@ -159,9 +669,82 @@ impl Value {
     }
 }
 
-fn build_topo(v: &Value, topo: &mut Vec<Value>, visited: &mut HashSet<Uuid>) {
-    if !visited.contains(&v.uuid()) {
-        visited.insert(v.uuid());
+/// softmax(x / T)_i = e^(x_i / T - max(x / T)) / sum_j e^(x_j / T - max(x / T))
+///
+/// A `temperature` T > 1 softens the distribution and T < 1 sharpens it; T = 1 is the
+/// plain softmax. The division by T is part of the graph. Subtracting the (constant)
+/// maximum does not change the result or the gradients but keeps every exponent <= 0.
+pub fn softmax(logits: &[Value], temperature: f64) -> Vec<Value> {
+    assert!(temperature > 0.0, "temperature must be positive");
+    let scaled: Vec<Value> = logits
+        .iter()
+        .map(|l| l.clone() * Value::new(1.0 / temperature))
+        .collect();
+    let max = scaled
+        .iter()
+        .map(|l| l.data())
+        .fold(f64::NEG_INFINITY, f64::max);
+    let exps: Vec<Value> = scaled
+        .into_iter()
+        .map(|l| (l - Value::new(max)).exp())
+        .collect();
+    let inv_sum = exps.iter().cloned().sum::<Value>().pow(&Value::new(-1.0));
+    exps.into_iter().map(|e| e * inv_sum.clone()).collect()
+}
+
+/// Differentiable arithmetic mean of `values`: their sum times the constant 1 / n.
+/// Panics on an empty slice, whose mean is undefined.
+pub fn mean(values: &[Value]) -> Value {
+    assert!(!values.is_empty(), "mean of an empty slice");
+    values.iter().cloned().sum::<Value>() * Value::new(1.0 / values.len() as f64)
+}
+
+/// Largest absolute difference between `fast_tanh` and `f64::tanh`.
+pub const FAST_TANH_TOLERANCE: f64 = 1e-4;
+
+/// Lambert's continued fraction for tanh truncated to a rational function of degree
+/// 7/6. It is accurate to ~1e-7 near zero and reaches 1 at |x| ~ 4.97, beyond which the
+/// result is clamped to ±1.
+pub fn fast_tanh(x: f64) -> f64 {
+    let x2 = x * x;
+    let num = x * (135135.0 + x2 * (17325.0 + x2 * (378.0 + x2)));
+    let den = 135135.0 + x2 * (62370.0 + x2 * (3150.0 + 28.0 * x2));
+    (num / den).clamp(-1.0, 1.0)
+}
+
+/// Logistic function that only ever exponentiates a non-positive number: for x < 0 it
+/// uses the equivalent form e^x / (1 + e^x), so e^-x cannot overflow.
+pub(crate) fn sigmoid(x: f64) -> f64 {
+    if x >= 0.0 {
+        1.0 / (1.0 + (-x).exp())
+    } else {
+        let e = x.exp();
+        e / (1.0 + e)
+    }
+}
+
+/// `x` with its magnitude raised to at least `eps`, keeping the sign (0 counts as
+/// positive).
+fn clamp_magnitude(x: f64, eps: f64) -> f64 {
+    if x.abs() >= eps {
+        x
+    } else if x < 0.0 {
+        -eps
+    } else {
+        eps
+    }
+}
+
+/// Grad of a backward closure's output node. Closures hold their output weakly, as a
+/// strong handle would form a cycle that keeps the whole graph alive; the node is
+/// always alive while its closure runs during `backward`.
+fn grad_of(out: &Weak<RefCell<InternalValue>>) -> f64 {
+    out.upgrade().map_or(0.0, |out| out.borrow().grad)
+}
+
+fn build_topo(v: &Value, topo: &mut Vec<Value>, visited: &mut HashSet<u64>) {
+    if !visited.contains(&v.id()) {
+        visited.insert(v.id());
         for child in &v.0.borrow().prev {
             build_topo(child, topo, visited);
         }
@@ -202,6 +785,12 @@ impl Sum for Value {
     }
 }
 
+impl Product for Value {
+    fn product<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Value::new(1.0), |product, val| product * val)
+    }
+}
+
 /// out = self + rfh.
 ///
 /// self.grad = dL/d(self) = dL/d(out) * d(out)/d(self)
@@ -221,17 +810,18 @@ impl Add for Value {
             prev.push(rhs);
         }
 
-        let out = Self::new_internal(data, 0.0, prev, None, Some(String::from("+")));
-        let out_internal = Rc::clone(&out.0);
+        let out = Self::new_internal(data, 0.0, prev, None, Some(Op::Add));
+        let out_internal = Rc::downgrade(&out.0);
 
         let backward = move || {
             let mut lhs = lhs_internal.borrow_mut();
-            let out_grad = out_internal.borrow().grad;
-            lhs.grad += out_grad;
+            let out_grad = grad_of(&out_internal);
 
             if is_self {
-                lhs.grad *= 2.0;
+                // a + a: both operands contribute, on top of what a already has
+                lhs.grad += 2.0 * out_grad;
             } else {
+                lhs.grad += out_grad;
                 rhs_internal.borrow_mut().grad += out_grad;
             }
         };
@@ -265,19 +855,17 @@ impl Sub for Value {
             prev.push(rhs);
         }
 
-        let out = Self::new_internal(data, 0.0, prev, None, Some(String::from("-")));
-        let out_internal = Rc::clone(&out.0);
+        let out = Self::new_internal(data, 0.0, prev, None, Some(Op::Sub));
+        let out_internal = Rc::downgrade(&out.0);
 
         let backward = move || {
-            let mut lhs = lhs_internal.borrow_mut();
-            let out_grad = out_internal.borrow().grad;
-            lhs.grad += out_grad;
-
+            // a - a: the two contributions cancel and leave a's gradient as it is
             if is_self {
-                lhs.grad *= 0.0;
-            } else {
-                rhs_internal.borrow_mut().grad -= out_grad;
+                return;
             }
+            let out_grad = grad_of(&out_internal);
+            lhs_internal.borrow_mut().grad += out_grad;
+            rhs_internal.borrow_mut().grad -= out_grad;
         };
 
         let out_internal = Rc::clone(&out.0);
@@ -306,24 +894,20 @@ impl Mul for Value {
             prev.push(rhs);
         }
 
-        let out = Self::new_internal(data, 0.0, prev, None, Some(String::from("*")));
-        let out_internal = Rc::clone(&out.0);
+        let out = Self::new_internal(data, 0.0, prev, None, Some(Op::Mul));
+        let out_internal = Rc::downgrade(&out.0);
 
         let backward = move || {
             let mut lhs = lhs_internal.borrow_mut();
-            let rhs_data = if is_self {
-                lhs.data
-            } else {
-                rhs_internal.borrow_mut().data
-            };
-
-            let out_grad = out_internal.borrow().grad;
-            lhs.grad += rhs_data * out_grad;
+            let out_grad = grad_of(&out_internal);
 
             if is_self {
-                lhs.grad *= 2.0;
+                // a * a: d(a^2)/da = 2a, added to what a already has
+                lhs.grad += 2.0 * lhs.data * out_grad;
             } else {
-                rhs_internal.borrow_mut().grad += lhs.data * out_grad;
+                let mut rhs = rhs_internal.borrow_mut();
+                lhs.grad += rhs.data * out_grad;
+                rhs.grad += lhs.data * out_grad;
             }
         };
 
@@ -334,11 +918,114 @@ impl Mul for Value {
     }
 }
 
+/// out = self / rhs, following `f64` semantics for a zero divisor (inf or NaN). Use
+/// `div_eps` to keep the result finite.
+impl Div for Value {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        self.div_eps(&rhs, 0.0)
+    }
+}
+
+/// out = -self, a single node unlike `Value::new(0.0) - self`.
+///
+/// self.grad = out.grad * -1
+impl Neg for Value {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        let data = -self.0.borrow().data;
+        self.unary(data, -1.0, Op::Neg)
+    }
+}
+
+/// `a += b` rebinds `a` to the node `a + b`, the same graph as `a = a + b`.
+impl AddAssign for Value {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = self.clone() + rhs;
+    }
+}
+
+/// `a *= b` rebinds `a` to the node `a * b`, the same graph as `a = a * b`.
+impl MulAssign for Value {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = self.clone() * rhs;
+    }
+}
+
+// Arithmetic with plain numbers. The number becomes a constant leaf whose gradient is
+// never read.
+
+impl Add<f64> for Value {
+    type Output = Self;
+
+    fn add(self, rhs: f64) -> Self::Output {
+        self + Value::new(rhs)
+    }
+}
+
+impl Add<Value> for f64 {
+    type Output = Value;
+
+    fn add(self, rhs: Value) -> Self::Output {
+        Value::new(self) + rhs
+    }
+}
+
+impl Sub<f64> for Value {
+    type Output = Self;
+
+    fn sub(self, rhs: f64) -> Self::Output {
+        self - Value::new(rhs)
+    }
+}
+
+impl Sub<Value> for f64 {
+    type Output = Value;
+
+    fn sub(self, rhs: Value) -> Self::Output {
+        Value::new(self) - rhs
+    }
+}
+
+impl Mul<f64> for Value {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        self * Value::new(rhs)
+    }
+}
+
+impl Mul<Value> for f64 {
+    type Output = Value;
+
+    fn mul(self, rhs: Value) -> Self::Output {
+        Value::new(self) * rhs
+    }
+}
+
+impl Div<f64> for Value {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self::Output {
+        self / Value::new(rhs)
+    }
+}
+
+impl Div<Value> for f64 {
+    type Output = Value;
+
+    fn div(self, rhs: Value) -> Self::Output {
+        Value::new(self) / rhs
+    }
+}
+
 type RcDataValue = Rc<DataValue>;
 
 #[derive(Clone)]
 pub struct DataValue {
-    pub uuid: Uuid,
+    pub id: u64,
     pub data: f64,
     pub grad: f64,
     pub label: String,
@@ -346,9 +1033,9 @@ pub struct DataValue {
 }
 
 impl DataValue {
-    pub fn new(uuid: Uuid, data: f64, grad: f64, label: String, op: Option<String>) -> Self {
+    pub fn new(id: u64, data: f64, grad: f64, label: String, op: Option<String>) -> Self {
         Self {
-            uuid,
+            id,
             data,
             grad,
             label,
@@ -360,7 +1047,7 @@ impl DataValue {
 impl From<&Value> for DataValue {
     fn from(value: &Value) -> Self {
         DataValue::new(
-            value.uuid(),
+            value.id(),
             value.data(),
             value.grad(),
             value.label(),
@@ -369,27 +1056,277 @@ impl From<&Value> for DataValue {
     }
 }
 
-#[derive(Clone)]
-struct InternalValue {
-    uuid: Uuid,
-    data: f64,
-    grad: f64,
-    prev: Vec<Value>,
-    label: Option<String>,
-    op: Option<String>,
-    backward: Option<Rc<RefCell<dyn FnMut()>>>,
+/// The operation that produced a node, kept in a structured form so that a graph
+/// can be serialized and rebuilt.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    /// Division with the divisor's magnitude clamped to at least the given eps.
+    Div(f64),
+    Neg,
+    Max,
+    Pow(f64),
+    Tanh,
+    FastTanh,
+    Sigmoid,
+    Exp,
+    Ln(f64),
+    /// `Ln(DEFAULT_EPS)` under the name "log".
+    Log,
+    Sqrt(f64),
+    Clamp(f64, f64),
+    Relu,
+    LeakyRelu(f64),
+    /// User defined function from `Value::map`, which cannot be serialized.
+    #[serde(skip)]
+    Map(fn(f64) -> f64, fn(f64) -> f64),
+    /// User defined binary op from `Value::custom_binary`, which cannot be serialized.
+    #[serde(skip)]
+    Custom {
+        name: String,
+        forward: fn(f64, f64) -> f64,
+        d_lhs: fn(f64, f64) -> f64,
+        d_rhs: fn(f64, f64) -> f64,
+    },
 }
 
-impl InternalValue {
-    pub fn new(
-        data: f64,
-        grad: f64,
-        prev: Vec<Value>,
-        label: Option<String>,
-        op: Option<String>,
-    ) -> Self {
+impl Op {
+    /// Forward computation on plain numbers, matching the `Value` methods. A single
+    /// argument to a binary op means the operand was combined with itself.
+    fn apply(&self, args: &[f64]) -> f64 {
+        let (a, b) = (args[0], args[args.len() - 1]);
+        match self {
+            Op::Add => a + b,
+            Op::Sub => a - b,
+            Op::Mul => a * b,
+            Op::Div(eps) => a / clamp_magnitude(b, *eps),
+            Op::Neg => -a,
+            Op::Max => a.max(b),
+            Op::Pow(degree) => a.powf(*degree),
+            Op::Tanh => a.tanh(),
+            Op::FastTanh => fast_tanh(a),
+            Op::Sigmoid => sigmoid(a),
+            Op::Exp => a.clamp(-EXP_CLAMP, EXP_CLAMP).exp(),
+            Op::Ln(eps) => a.max(*eps).ln(),
+            Op::Log => a.max(DEFAULT_EPS).ln(),
+            Op::Sqrt(eps) => a.max(*eps).sqrt(),
+            Op::Clamp(lo, hi) => a.clamp(*lo, *hi),
+            Op::Relu => a.max(0.0),
+            Op::LeakyRelu(alpha) => {
+                if a > 0.0 {
+                    a
+                } else {
+                    alpha * a
+                }
+            }
+            Op::Map(forward, _) => forward(a),
+            Op::Custom { forward, .. } => forward(a, b),
+        }
+    }
+
+    /// Local derivatives d(out)/d(arg) for each of `args`, given the result `out` of
+    /// `apply`. A binary op with a single argument combined it with itself, so the
+    /// derivatives with respect to both operands are added up.
+    fn local_grads(&self, args: &[f64], out: f64) -> Vec<f64> {
+        let (a, b) = (args[0], args[args.len() - 1]);
+        let binary = |da: f64, db: f64| {
+            if args.len() == 1 {
+                vec![da + db]
+            } else {
+                vec![da, db]
+            }
+        };
+        match self {
+            Op::Add => binary(1.0, 1.0),
+            Op::Sub => binary(1.0, -1.0),
+            Op::Mul => binary(b, a),
+            Op::Div(eps) => {
+                let d = clamp_magnitude(b, *eps);
+                if args.len() == 1 {
+                    vec![(1.0 - a / d) / d]
+                } else {
+                    vec![1.0 / d, -a / (d * d)]
+                }
+            }
+            Op::Neg => vec![-1.0],
+            Op::Max if args.len() == 1 => vec![1.0],
+            Op::Max if a >= b => vec![1.0, 0.0],
+            Op::Max => vec![0.0, 1.0],
+            Op::Pow(degree) => vec![degree * a.powf(degree - 1.0)],
+            Op::Tanh | Op::FastTanh => vec![1.0 - out * out],
+            Op::Sigmoid => vec![out * (1.0 - out)],
+            Op::Exp => vec![out],
+            Op::Ln(eps) => vec![1.0 / a.max(*eps)],
+            Op::Log => vec![1.0 / a.max(DEFAULT_EPS)],
+            Op::Sqrt(_) => vec![0.5 / out],
+            Op::Clamp(lo, hi) => vec![if (*lo..=*hi).contains(&a) { 1.0 } else { 0.0 }],
+            Op::Relu => vec![if a > 0.0 { 1.0 } else { 0.0 }],
+            Op::LeakyRelu(alpha) => vec![if a > 0.0 { 1.0 } else { *alpha }],
+            Op::Map(_, backward_local) => vec![backward_local(a)],
+            Op::Custom { d_lhs, d_rhs, .. } => binary(d_lhs(a, b), d_rhs(a, b)),
+        }
+    }
+
+    /// `local_grads` as `Value`s built from the operands `args` and the node `out`, for
+    /// `Value::grad_graph`. Piecewise linear and user defined ops get constant local
+    /// derivatives, i.e. their second derivative is taken to be zero.
+    fn local_grad_values(&self, args: &[Value], out: &Value) -> Vec<Value> {
+        let (a, b) = (&args[0], &args[args.len() - 1]);
+        // an operand raised to at least `eps` in magnitude, as a constant if clamped
+        let clamped = |x: &Value, eps: f64| {
+            if x.data().abs() >= eps {
+                x.clone()
+            } else {
+                Value::new(clamp_magnitude(x.data(), eps))
+            }
+        };
+        match self {
+            Op::Mul if args.len() == 1 => vec![2.0 * a.clone()],
+            Op::Mul => vec![b.clone(), a.clone()],
+            Op::Div(eps) => {
+                let d = clamped(b, *eps);
+                if args.len() == 1 {
+                    vec![(1.0 - a.clone() / d.clone()) / d]
+                } else {
+                    vec![1.0 / d.clone(), -a.clone() / (d.clone() * d)]
+                }
+            }
+            Op::Pow(degree) => vec![*degree * a.pow(&Value::new(degree - 1.0))],
+            Op::Tanh | Op::FastTanh => vec![1.0 - out.clone() * out.clone()],
+            Op::Sigmoid => vec![out.clone() * (1.0 - out.clone())],
+            Op::Exp => vec![out.clone()],
+            Op::Ln(eps) if a.data() >= *eps => vec![1.0 / a.clone()],
+            Op::Log if a.data() >= DEFAULT_EPS => vec![1.0 / a.clone()],
+            Op::Sqrt(_) => vec![0.5 / out.clone()],
+            _ => {
+                let data: Vec<f64> = args.iter().map(|x| x.data()).collect();
+                self.local_grads(&data, out.data())
+                    .into_iter()
+                    .map(Value::new)
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Op::Add => write!(f, "+"),
+            Op::Sub => write!(f, "-"),
+            Op::Mul => write!(f, "*"),
+            Op::Div(_) => write!(f, "/"),
+            Op::Neg => write!(f, "neg"),
+            Op::Max => write!(f, "max"),
+            Op::Pow(degree) => write!(f, "**{}", degree),
+            Op::Tanh => write!(f, "tanh"),
+            Op::FastTanh => write!(f, "tanh_fast"),
+            Op::Sigmoid => write!(f, "sigmoid"),
+            Op::Exp => write!(f, "exp"),
+            Op::Ln(_) => write!(f, "ln"),
+            Op::Log => write!(f, "log"),
+            Op::Sqrt(_) => write!(f, "sqrt"),
+            Op::Clamp(..) => write!(f, "clamp"),
+            Op::Relu => write!(f, "relu"),
+            Op::LeakyRelu(_) => write!(f, "leaky_relu"),
+            Op::Map(..) => write!(f, "map"),
+            Op::Custom { name, .. } => write!(f, "{}", name),
+        }
+    }
+}
+
+/// A graph flattened by `Value::compile` into a list of instructions on plain numbers.
+pub struct CompiledFn {
+    leaves: Vec<u64>,
+    /// One entry per non-leaf node in topological order: the op and the slots of its
+    /// operands. Slots `0..leaves.len()` hold the inputs, the following ones hold the
+    /// results of the instructions.
+    instructions: Vec<(Op, Vec<usize>)>,
+}
+
+impl CompiledFn {
+    /// Ids of the leaves, in the order `call` expects their values.
+    pub fn leaves(&self) -> &[u64] {
+        &self.leaves
+    }
+
+    pub fn call(&self, inputs: &[f64]) -> f64 {
+        assert_eq!(inputs.len(), self.leaves.len(), "one input per leaf");
+        let mut slots = Vec::with_capacity(self.leaves.len() + self.instructions.len());
+        slots.extend_from_slice(inputs);
+        for (op, args) in &self.instructions {
+            let args: Vec<f64> = args.iter().map(|&i| slots[i]).collect();
+            slots.push(op.apply(&args));
+        }
+        slots[slots.len() - 1]
+    }
+
+    /// Gradients of the output with respect to every input, by a forward pass over the
+    /// instructions followed by a reverse pass accumulating the local derivatives.
+    pub fn gradient(&self, inputs: &[f64]) -> Vec<f64> {
+        assert_eq!(inputs.len(), self.leaves.len(), "one input per leaf");
+        let mut slots = Vec::with_capacity(self.leaves.len() + self.instructions.len());
+        slots.extend_from_slice(inputs);
+        for (op, args) in &self.instructions {
+            let args: Vec<f64> = args.iter().map(|&i| slots[i]).collect();
+            slots.push(op.apply(&args));
+        }
+
+        let mut grads = vec![0.0; slots.len()];
+        if let Some(last) = grads.last_mut() {
+            *last = 1.0;
+        }
+        for (i, (op, args)) in self.instructions.iter().enumerate().rev() {
+            let slot = self.leaves.len() + i;
+            let values: Vec<f64> = args.iter().map(|&a| slots[a]).collect();
+            for (&arg, local) in args.iter().zip(op.local_grads(&values, slots[slot])) {
+                grads[arg] += local * grads[slot];
+            }
+        }
+        grads.truncate(self.leaves.len());
+        grads
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ExprNode {
+    id: u64,
+    data: f64,
+    label: Option<String>,
+    op: Option<Op>,
+    args: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Expr {
+    root: u64,
+    nodes: Vec<ExprNode>,
+}
+
+#[derive(Clone)]
+struct InternalValue {
+    id: u64,
+    data: f64,
+    grad: f64,
+    prev: Vec<Value>,
+    label: Option<String>,
+    op: Option<Op>,
+    backward: Option<Rc<RefCell<dyn FnMut()>>>,
+}
+
+impl InternalValue {
+    pub fn new(
+        data: f64,
+        grad: f64,
+        prev: Vec<Value>,
+        label: Option<String>,
+        op: Option<Op>,
+    ) -> Self {
+        NODES_CREATED.with(|n| n.set(n.get() + 1));
         Self {
-            uuid: Uuid::new_v4(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
             data,
             grad,
             prev,
@@ -402,8 +1339,10 @@ impl InternalValue {
 
 #[cfg(test)]
 mod tests {
-    use crate::value::Value;
+    use crate::value::{mean, softmax, Value, FAST_TANH_TOLERANCE};
+    use crate::DEFAULT_EPS;
     use assert_approx_eq::assert_approx_eq;
+    use std::rc::Rc;
 
     #[test]
     fn final_grad() {
@@ -448,6 +1387,28 @@ mod tests {
         assert_eq!(c.grad(), 1.0);
     }
 
+    #[test]
+    fn add_self_with_other_uses() {
+        // f = (a + a) + a * b, df/da = 2 + b, for both orders of the branches
+        for self_first in [true, false] {
+            let a = Value::new(3.0);
+            let b = Value::new(-4.0);
+            let double = a.clone() + a.clone();
+            let product = a.clone() * b.clone();
+            let f = if self_first {
+                double + product
+            } else {
+                product + double
+            };
+
+            f.backward();
+
+            assert_eq!(f.data(), -6.0);
+            assert_eq!(a.grad(), 2.0 + b.data());
+            assert_eq!(b.grad(), a.data());
+        }
+    }
+
     #[test]
     fn sub() {
         let a = Value::new(3.0);
@@ -480,6 +1441,28 @@ mod tests {
         assert_eq!(c.grad(), 1.0);
     }
 
+    #[test]
+    fn sub_self_with_other_uses() {
+        // f = (a - a) + a * c, df/da = c, for both orders of the branches
+        for self_first in [true, false] {
+            let a = Value::new(3.0);
+            let c = Value::new(-4.0);
+            let zero = a.clone() - a.clone();
+            let product = a.clone() * c.clone();
+            let f = if self_first {
+                zero + product
+            } else {
+                product + zero
+            };
+
+            f.backward();
+
+            assert_eq!(f.data(), -12.0);
+            assert_eq!(a.grad(), c.data());
+            assert_eq!(c.grad(), a.data());
+        }
+    }
+
     #[test]
     fn mul() {
         let a = Value::new(3.0);
@@ -512,6 +1495,218 @@ mod tests {
         assert_eq!(c.grad(), 1.0);
     }
 
+    #[test]
+    fn product() {
+        let inputs: Vec<Value> = [2.0, 3.0, 4.0].into_iter().map(Value::new).collect();
+        let product: Value = inputs.iter().cloned().product();
+
+        product.backward();
+
+        assert_eq!(product.data(), 24.0);
+        assert_eq!(inputs[0].grad(), 12.0);
+        assert_eq!(inputs[1].grad(), 8.0);
+        assert_eq!(inputs[2].grad(), 6.0);
+    }
+
+    #[test]
+    fn mean_of_values() {
+        let inputs: Vec<Value> = [1.0, 2.0, 4.0, 9.0].into_iter().map(Value::new).collect();
+        let avg = mean(&inputs);
+
+        avg.backward();
+
+        assert_eq!(avg.data(), 4.0);
+        assert!(inputs.iter().all(|x| x.grad() == 0.25));
+    }
+
+    #[test]
+    #[should_panic(expected = "mean of an empty slice")]
+    fn mean_of_nothing() {
+        mean(&[]);
+    }
+
+    #[test]
+    fn add_assign() {
+        let inputs: Vec<Value> = [1.0, 2.0, 3.0].into_iter().map(Value::new).collect();
+        let mut total = inputs[0].clone();
+        total += inputs[1].clone();
+        total += inputs[2].clone();
+
+        total.backward();
+
+        assert_eq!(total.data(), 6.0);
+        assert!(inputs.iter().all(|x| x.grad() == 1.0));
+        assert_eq!(total.trace().0.len(), 5);
+    }
+
+    #[test]
+    fn mul_assign() {
+        let a = Value::new(2.0);
+        let b = Value::new(-3.0);
+        let mut scale = a.clone();
+        scale *= b.clone();
+
+        scale.backward();
+
+        assert_eq!(scale.data(), -6.0);
+        assert_eq!(a.grad(), -3.0);
+        assert_eq!(b.grad(), 2.0);
+    }
+
+    #[test]
+    fn scalar_arithmetic() {
+        let a = Value::new(3.0);
+        let c = a.clone() * 2.0;
+        c.backward();
+        assert_eq!(c.data(), 6.0);
+        assert_eq!(a.grad(), 2.0);
+
+        let b = Value::new(1.0);
+        let d = 2.0 - b.clone();
+        d.backward();
+        assert_eq!(d.data(), 1.0);
+        assert_eq!(b.grad(), -1.0);
+
+        let x = Value::new(4.0);
+        let y = (x.clone() + 1.0) / 2.0 - 0.5 + 3.0 * x.clone() / 4.0 + 1.0 / x.clone();
+        y.backward();
+        assert_eq!(y.data(), 5.25);
+        assert_eq!(x.grad(), 0.5 + 0.75 - 1.0 / 16.0);
+    }
+
+    #[test]
+    fn neg() {
+        let x = Value::new(3.0);
+        let y = -x.clone();
+
+        y.backward();
+
+        assert_eq!(y.data(), -3.0);
+        assert_eq!(x.grad(), -1.0);
+        assert_eq!(y.op(), Some("neg".to_string()));
+        assert_eq!(y.trace().0.len(), 2);
+        assert_eq!((Value::new(0.0) - x).trace().0.len(), 3);
+    }
+
+    #[test]
+    fn neg_chain() {
+        let a = Value::new(-2.0);
+        let b = Value::new(3.0);
+        let c = -(a.clone() * b.clone()) + Value::new(1.0);
+
+        c.backward();
+
+        assert_eq!(c.data(), 7.0);
+        assert_eq!(a.grad(), -3.0);
+        assert_eq!(b.grad(), 2.0);
+        let rebuilt = Value::from_expr_json(&c.to_expr_json().unwrap()).unwrap();
+        assert_eq!(rebuilt.data(), 7.0);
+    }
+
+    #[test]
+    fn mul_self_with_other_uses() {
+        // f = a * a + a * b, df/da = 2a + b, for both orders of the branches
+        for self_first in [true, false] {
+            let a = Value::new(3.0);
+            let b = Value::new(-4.0);
+            let square = a.clone() * a.clone();
+            let product = a.clone() * b.clone();
+            let f = if self_first {
+                square + product
+            } else {
+                product + square
+            };
+
+            f.backward();
+
+            assert_eq!(f.data(), -3.0);
+            assert_eq!(a.grad(), 2.0 * a.data() + b.data());
+            assert_eq!(b.grad(), a.data());
+        }
+    }
+
+    #[test]
+    fn div() {
+        let a = Value::new(3.0);
+        let b = Value::new(4.0);
+        let c = a.clone() / b.clone();
+
+        c.backward();
+
+        assert_eq!(a.data(), 3.0);
+        assert_eq!(a.grad(), 0.25);
+
+        assert_eq!(b.data(), 4.0);
+        assert_eq!(b.grad(), -3.0 / 16.0);
+
+        assert_eq!(c.data(), 0.75);
+        assert_eq!(c.grad(), 1.0);
+        assert_eq!(c.op(), Some("/".to_string()));
+    }
+
+    #[test]
+    fn div_self() {
+        let a = Value::new(3.0);
+        let c = a.clone() / a.clone();
+
+        c.backward();
+
+        assert_eq!(a.data(), 3.0);
+        assert_eq!(a.grad(), 0.0);
+
+        assert_eq!(c.data(), 1.0);
+        assert_eq!(c.grad(), 1.0);
+    }
+
+    #[test]
+    fn div_eps() {
+        let a = Value::new(2.0);
+        let zero = Value::new(0.0);
+        let c = a.div_eps(&zero, 1e-3);
+        c.backward();
+        assert_eq!(c.data(), 2000.0);
+        assert_eq!(a.grad(), 1000.0);
+        assert_eq!(zero.grad(), -2e6);
+
+        // the sign of a small divisor is kept, large ones are untouched
+        assert_eq!(a.div_eps(&Value::new(-1e-6), 1e-3).data(), -2000.0);
+        assert_eq!(a.div_eps(&Value::new(4.0), 1e-3).data(), 0.5);
+
+        let b = Value::new(0.0);
+        let c = b.div_eps(&b, 0.5);
+        c.backward();
+        assert_eq!(c.data(), 0.0);
+        assert_eq!(b.grad(), 2.0);
+        let rebuilt = Value::from_expr_json(&c.to_expr_json().unwrap()).unwrap();
+        assert_eq!(rebuilt.data(), 0.0);
+    }
+
+    #[test]
+    fn div_chain() {
+        // f = (a + b) / c
+        let a = Value::new(1.0);
+        let b = Value::new(2.0);
+        let c = Value::new(-4.0);
+        let f = (a.clone() + b.clone()) / c.clone();
+
+        f.backward();
+
+        assert_eq!(f.data(), -0.75);
+        assert_eq!(a.grad(), -0.25);
+        assert_eq!(b.grad(), -0.25);
+        assert_eq!(c.grad(), -3.0 / 16.0);
+        assert_approx_eq!(f.compile().gradient(&[1.0, 2.0, -4.0])[2], -3.0 / 16.0);
+    }
+
+    #[test]
+    fn div_by_zero() {
+        let inf = Value::new(1.0) / Value::new(0.0);
+        assert_eq!(inf.data(), f64::INFINITY);
+        let nan = Value::new(0.0) / Value::new(0.0);
+        assert!(nan.data().is_nan());
+        nan.backward();
+    }
+
     #[test]
     fn chain() {
         let a = Value::new(-2.0);
@@ -552,6 +1747,37 @@ mod tests {
         assert_eq!(c.grad(), 1.0);
     }
 
+    #[test]
+    fn pow_square_and_root() {
+        let a = Value::new(-3.0);
+        let square = a.pow(&Value::new(2.0));
+        square.backward();
+        assert_eq!(square.data(), 9.0);
+        assert_eq!(a.grad(), -6.0);
+        assert_eq!(square.op(), Some("**2".to_string()));
+
+        let b = Value::new(16.0);
+        let exponent = Value::new(0.5);
+        let root = b.pow(&exponent);
+        root.backward();
+        assert_eq!(root.data(), 4.0);
+        assert_eq!(b.grad(), 0.125);
+        // the exponent is a constant, not part of the graph
+        assert_eq!(root.trace().0.len(), 2);
+        assert_eq!(exponent.grad(), 0.0);
+    }
+
+    #[test]
+    fn pow_finite_differences() {
+        let h = 1e-6;
+        for (x, degree) in [(1.7, 3.0), (0.4, -1.5), (2.5, 0.5)] {
+            let a = Value::new(x);
+            a.pow(&Value::new(degree)).backward();
+            let numeric = ((x + h).powf(degree) - (x - h).powf(degree)) / (2.0 * h);
+            assert_approx_eq!(a.grad(), numeric, 1e-6);
+        }
+    }
+
     #[test]
     fn tanh() {
         let a = Value::new(0.8814);
@@ -562,7 +1788,596 @@ mod tests {
         assert_eq!(a.data(), 0.8814);
         assert_approx_eq!(a.grad(), 0.5, 0.1);
 
-        assert_approx_eq!(c.data(), 0.7071, 0.0001);
+        assert_approx_eq!(c.data(), std::f64::consts::FRAC_1_SQRT_2, 0.0001);
         assert_eq!(c.grad(), 1.0);
     }
+
+    #[test]
+    fn release() {
+        let a = Value::new(2.0);
+        let b = Value::new(-3.0);
+        let c = a.clone() * b.clone();
+        let d = c.clone().tanh();
+        d.backward();
+
+        // c is held by the variable, by d as an operand, and by d's backward closure
+        assert_eq!(Rc::strong_count(&c.0), 3);
+        d.release();
+        assert_eq!(Rc::strong_count(&c.0), 2);
+
+        let c_node = Rc::downgrade(&c.0);
+        let d_node = Rc::downgrade(&d.0);
+        drop(c);
+        drop(d);
+        assert!(c_node.upgrade().is_none());
+        assert!(d_node.upgrade().is_none());
+        assert_eq!(Rc::strong_count(&a.0), 1);
+    }
+
+    #[test]
+    fn graph_is_freed_when_dropped() {
+        let a = Value::new(2.0);
+        let b = Value::new(-3.0);
+        let c = a.clone() * b.clone();
+        let d = (c.clone() + a.clone()).tanh();
+        d.backward();
+
+        // a backward closure does not keep its own node alive
+        assert_eq!(Rc::strong_count(&d.0), 1);
+        let c_node = Rc::downgrade(&c.0);
+        let d_node = Rc::downgrade(&d.0);
+        drop(c);
+        drop(d);
+        assert!(c_node.upgrade().is_none());
+        assert!(d_node.upgrade().is_none());
+        assert_eq!(Rc::strong_count(&a.0), 1);
+        assert_eq!(Rc::strong_count(&b.0), 1);
+    }
+
+    #[test]
+    fn adjacency() {
+        let a = Value::new(2.0);
+        let b = Value::new(-3.0);
+        let c = Value::new(10.0).with_label("c");
+        let ab = a * b;
+        let d = ab.clone() + c.clone();
+
+        let (nodes, children) = d.adjacency();
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(nodes[0].id, d.id());
+        let child_ids: Vec<u64> = children[0].iter().map(|&i| nodes[i].id).collect();
+        assert_eq!(child_ids, vec![ab.id(), c.id()]);
+        assert_eq!(nodes[children[0][0]].op.as_deref(), Some("*"));
+        assert_eq!(nodes[children[0][1]].label, "c");
+        assert!(children[children[0][1]].is_empty());
+
+        let trace_ids: Vec<u64> = d.trace().0.iter().map(|n| n.id).collect();
+        assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), trace_ids);
+    }
+
+    #[test]
+    fn dependencies() {
+        let a = Value::new(1.0);
+        let b = Value::new(2.0);
+        let c = Value::new(3.0);
+        let d = a.clone() * b.clone();
+        let _e = d.clone() + c;
+
+        let deps = d.dependencies();
+
+        assert_eq!(deps, vec![a.id(), b.id()]);
+        assert_eq!(a.dependencies(), vec![a.id()]);
+    }
+
+    #[test]
+    fn unused_leaves() {
+        let inputs: Vec<Value> = (0..3).map(|i| Value::new(i as f64)).collect();
+        let out = inputs[0].clone() * Value::new(2.0) + inputs[1].tanh();
+        let unused = out.unused_leaves(&inputs);
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].id(), inputs[2].id());
+        assert!(out.unused_leaves(&inputs[..2]).is_empty());
+    }
+
+    #[test]
+    fn backward_trace() {
+        let a = Value::new(3.0);
+        let b = Value::new(4.0);
+        let c = a.clone() * b.clone();
+
+        let steps = c.backward_trace();
+
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[0], (c.id(), 1.0));
+        assert!(steps[1..].contains(&(a.id(), 4.0)));
+        assert!(steps[1..].contains(&(b.id(), 3.0)));
+    }
+
+    #[test]
+    fn save_dot() {
+        let a = Value::new(2.0).with_label("a");
+        let l = (a * Value::new(-3.0)).with_label("L");
+        let path = std::env::temp_dir().join("micrograd_save_dot_test.dot");
+        let path = path.to_str().unwrap();
+
+        l.save_dot(path).unwrap();
+        let dot = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(dot, l.to_dot());
+        assert!(dot.starts_with("digraph {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert!(dot.contains("{ L | data -6.0000"));
+    }
+
+    #[test]
+    fn expr_json_round_trip() {
+        let a = Value::new(2.0).with_label("a");
+        let b = Value::new(-3.0).with_label("b");
+        let c = Value::new(0.5).with_label("c");
+        let e = (a.clone() * b.clone()).with_label("e");
+        let f = (e.clone() + c.clone()).tanh().with_label("f");
+        // e is shared between f and the root
+        let l = (e.clone() * f.pow(&Value::new(2.0)) - c.ln().exp()).with_label("L");
+        l.backward();
+
+        let json = l.to_expr_json().unwrap();
+        let reloaded = Value::from_expr_json(&json).unwrap();
+        reloaded.backward();
+
+        let (nodes, edges) = l.trace();
+        let (reloaded_nodes, reloaded_edges) = reloaded.trace();
+        assert_eq!(nodes.len(), reloaded_nodes.len());
+        assert_eq!(edges.len(), reloaded_edges.len());
+        assert_eq!(reloaded_nodes.iter().filter(|n| n.label == "e").count(), 1);
+        for (n, r) in nodes.iter().zip(&reloaded_nodes) {
+            assert_eq!(n.label, r.label);
+            assert_eq!(n.op, r.op);
+            assert_eq!(n.data, r.data);
+            assert_eq!(n.grad, r.grad);
+        }
+    }
+
+    #[test]
+    fn expr_json_map() {
+        let a = Value::new(2.0).map(|x| x, |_| 1.0);
+        assert!(a.to_expr_json().is_err());
+        assert!(Value::from_expr_json(r#"{"root":1,"nodes":[]}"#).is_err());
+    }
+
+    #[test]
+    fn compile() {
+        let build = |x: &[f64]| {
+            let a = Value::new(x[0]);
+            let b = Value::new(x[1]);
+            let c = Value::new(x[2]);
+            let e = a.clone() * b.clone();
+            (e.clone() + c.clone()).tanh() * e.pow(&Value::new(2.0))
+                - c.exp().map(|x| x * x, |x| 2.0 * x)
+                + (a.clone() + a).sqrt().ln()
+        };
+
+        // leaves in dependency order: a, b, c
+        let out = build(&[1.0, 2.0, 3.0]);
+        let compiled = out.compile();
+        assert_eq!(compiled.leaves(), &out.dependencies()[..]);
+        assert_eq!(compiled.call(&[1.0, 2.0, 3.0]), out.data());
+
+        for x in [[0.5, -1.0, 2.0], [3.0, 0.1, -0.7], [-2.0, -2.0, 0.0]] {
+            assert_approx_eq!(compiled.call(&x), build(&x).data(), 1e-12);
+        }
+    }
+
+    #[test]
+    fn compile_backward() {
+        let build = |x: &[f64]| {
+            let a = Value::new(x[0]);
+            let b = Value::new(x[1]);
+            let c = Value::new(x[2]);
+            let e = a.clone() * b.clone();
+            let out = (e.clone() + c.clone()).tanh() * e.pow(&Value::new(2.0))
+                - c.exp().map(|x| x * x, |x| 2.0 * x)
+                + (a.clone() + b.clone()).sqrt().ln()
+                + b.max(&c).clamp(-1.0, 1.0);
+            (out, [a, b, c])
+        };
+
+        let (out, _) = build(&[1.0, 2.0, 3.0]);
+        let gradient = out.compile_backward();
+        for x in [[0.5, 1.0, 2.0], [3.0, 0.1, -0.7], [1.5, 0.5, 0.2]] {
+            let (out, leaves) = build(&x);
+            out.backward();
+            for (g, leaf) in gradient(&x).iter().zip(&leaves) {
+                assert_approx_eq!(g, leaf.grad(), 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn grad_graph() {
+        let a = Value::new(0.7);
+        let b = Value::new(-1.3);
+        let c = Value::new(2.0);
+        let e = a.clone() * b.clone();
+        let out = (e.clone() + c.clone()).tanh() * e.pow(&Value::new(3.0))
+            + (a.clone() * a.clone()).sigmoid() / (c.clone() - b.clone()).sqrt()
+            - c.exp().ln() * b.relu()
+            + (a.clone() - c.clone()).max(&b);
+        let unused = Value::new(1.0);
+
+        let grads = out.grad_graph(&[a.clone(), b.clone(), c.clone(), unused.clone()]);
+        assert!([&a, &b, &c].iter().all(|v| v.grad() == 0.0));
+        out.backward();
+        for (g, v) in grads.iter().zip([&a, &b, &c]) {
+            assert_approx_eq!(g.data(), v.grad(), 1e-12);
+        }
+        assert_eq!(grads[3].data(), 0.0);
+
+        // d^2/dx^2 tanh(x) = -2 * tanh(x) * (1 - tanh(x)^2)
+        let x = Value::new(0.4);
+        let dx = x.tanh().grad_graph(std::slice::from_ref(&x)).remove(0);
+        dx.backward();
+        let t = 0.4f64.tanh();
+        assert_approx_eq!(x.grad(), -2.0 * t * (1.0 - t * t), 1e-12);
+
+        // d^2/dx^2 x^3 / y = 6x / y and d^2/dxdy x^3 / y = -3x^2 / y^2
+        let x = Value::new(1.5);
+        let y = Value::new(-2.0);
+        let dx = (x.pow(&Value::new(3.0)) / y.clone())
+            .grad_graph(std::slice::from_ref(&x))
+            .remove(0);
+        dx.backward();
+        assert_approx_eq!(x.grad(), 6.0 * 1.5 / -2.0, 1e-12);
+        assert_approx_eq!(y.grad(), -3.0 * 1.5 * 1.5 / 4.0, 1e-12);
+    }
+
+    #[test]
+    fn vanishing_nodes() {
+        let w = Value::new(5.0);
+        let x = Value::new(4.0);
+        let b = Value::new(0.1);
+        let z = w.clone() * x.clone();
+        let out = z.tanh() + b.clone();
+        out.backward();
+
+        let vanishing = out.vanishing_nodes(1e-6);
+
+        assert!(vanishing.contains(&z.id()));
+        assert!(vanishing.contains(&w.id()));
+        assert!(!vanishing.contains(&out.id()));
+        assert!(!vanishing.contains(&b.id()));
+    }
+
+    #[test]
+    fn ids_are_unique() {
+        let a = Value::new(1.0);
+        let b = Value::new(1.0);
+        let c = a.clone() + b.clone();
+        assert!(a.id() < b.id() && b.id() < c.id());
+        assert_eq!(a.clone().id(), a.id());
+    }
+
+    #[test]
+    fn ln() {
+        let a = Value::new(2.0);
+        let c = a.ln();
+
+        c.backward();
+
+        assert_approx_eq!(c.data(), 2f64.ln());
+        assert_approx_eq!(a.grad(), 0.5);
+    }
+
+    #[test]
+    fn log() {
+        let a = Value::new(4.0);
+        let c = a.log();
+
+        c.backward();
+
+        assert_approx_eq!(c.data(), 4f64.ln());
+        assert_eq!(a.grad(), 0.25);
+        assert_eq!(c.op().as_deref(), Some("log"));
+        let rebuilt = Value::from_expr_json(&c.to_expr_json().unwrap()).unwrap();
+        assert_eq!(rebuilt.op().as_deref(), Some("log"));
+        assert_eq!(rebuilt.data(), c.data());
+    }
+
+    #[test]
+    fn log_non_positive() {
+        for x in [0.0, -1.0] {
+            let a = Value::new(x);
+            let c = a.log();
+            c.backward();
+            assert_eq!(c.data(), DEFAULT_EPS.ln());
+            assert_eq!(a.grad(), 1.0 / DEFAULT_EPS);
+        }
+    }
+
+    #[test]
+    fn ln_eps() {
+        let a = Value::new(1e-9);
+        let c = a.ln_eps(0.1);
+
+        c.backward();
+
+        assert_approx_eq!(c.data(), 0.1f64.ln());
+        assert_approx_eq!(a.grad(), 10.0);
+
+        let b = Value::new(0.0);
+        let c = b.ln();
+        c.backward();
+        assert!(c.data().is_finite());
+        assert!(b.grad().is_finite());
+    }
+
+    #[test]
+    fn relu() {
+        let negative = Value::new(-2.0);
+        let out = negative.relu();
+        out.backward();
+        assert_eq!(out.data(), 0.0);
+        assert_eq!(negative.grad(), 0.0);
+        assert_eq!(out.op(), Some("relu".to_string()));
+
+        let positive = Value::new(1.5);
+        let out = positive.relu();
+        out.backward();
+        assert_eq!(out.data(), 1.5);
+        assert_eq!(positive.grad(), 1.0);
+    }
+
+    #[test]
+    fn leaky_relu() {
+        let negative = Value::new(-2.0);
+        let out = negative.leaky_relu(0.1);
+        out.backward();
+        assert_eq!(out.data(), -0.2);
+        assert_eq!(negative.grad(), 0.1);
+        assert_eq!(out.op(), Some("leaky_relu".to_string()));
+
+        let positive = Value::new(1.5);
+        positive.leaky_relu(0.1).backward();
+        assert_eq!(positive.grad(), 1.0);
+    }
+
+    #[test]
+    fn relu_two_layers() {
+        // out = w2 * relu(w1 * x + b1), with the hidden unit active
+        let x = Value::new(2.0);
+        let w1 = Value::new(0.5);
+        let b1 = Value::new(-0.5);
+        let w2 = Value::new(-3.0);
+        let out = w2.clone() * (w1.clone() * x.clone() + b1.clone()).relu();
+
+        out.backward();
+
+        assert_eq!(out.data(), -1.5);
+        assert_eq!(w2.grad(), 0.5);
+        assert_eq!(w1.grad(), -6.0);
+        assert_eq!(b1.grad(), -3.0);
+        let compiled = out.compile();
+        assert_eq!(compiled.call(&[-3.0, 0.5, 2.0, -0.5]), -1.5);
+
+        // with the hidden unit inactive nothing below it gets a gradient
+        b1.set_data(-2.0);
+        let out = w2.clone() * (w1.clone() * x.clone() + b1.clone()).relu();
+        w1.zero_grad();
+        out.backward();
+        assert_eq!(out.data(), 0.0);
+        assert_eq!(w1.grad(), 0.0);
+    }
+
+    #[test]
+    fn max() {
+        let a = Value::new(3.0);
+        let b = Value::new(4.0);
+        let c = a.max(&b) * Value::new(2.0);
+
+        c.backward();
+
+        assert_eq!(c.data(), 8.0);
+        assert_eq!(a.grad(), 0.0);
+        assert_eq!(b.grad(), 2.0);
+
+        let d = a.max(&a);
+        d.backward();
+        assert_eq!(d.data(), 3.0);
+        assert_eq!(a.grad(), 1.0);
+    }
+
+    #[test]
+    fn map() {
+        let a = Value::new(3.0);
+        let b = Value::new(2.0);
+        let c = a.map(|x| x * x, |x| 2.0 * x) * b.clone();
+
+        c.backward();
+
+        assert_eq!(c.data(), 18.0);
+        assert_eq!(a.grad(), 12.0);
+        assert_eq!(b.grad(), 9.0);
+    }
+
+    #[test]
+    fn exp() {
+        let a = Value::new(2.0);
+        let c = a.exp();
+
+        c.backward();
+
+        assert_approx_eq!(c.data(), 2f64.exp());
+        assert_approx_eq!(a.grad(), 2f64.exp());
+
+        let b = Value::new(1000.0);
+        let c = b.exp();
+        c.backward();
+        assert!(c.data().is_finite());
+        assert!(b.grad().is_finite());
+    }
+
+    #[test]
+    fn sigmoid() {
+        let zero = Value::new(0.0);
+        let out = zero.sigmoid();
+        out.backward();
+        assert_eq!(out.data(), 0.5);
+        assert_eq!(zero.grad(), 0.25);
+
+        let a = Value::new(-1.3);
+        let s = a.sigmoid();
+        s.backward();
+        assert_approx_eq!(s.data(), 1.0 / (1.0 + 1.3f64.exp()));
+        assert_approx_eq!(a.grad(), s.data() * (1.0 - s.data()));
+        assert_eq!(s.op(), Some("sigmoid".to_string()));
+    }
+
+    #[test]
+    fn sigmoid_extreme_inputs() {
+        let low = Value::new(-100.0).sigmoid();
+        assert!(low.data() > 0.0 && low.data() < 1e-40);
+        // 1 - e^-100 is closer to 1 than f64 can resolve, so this rounds to exactly 1
+        let high = Value::new(100.0).sigmoid();
+        assert!(high.data() > 0.5 && high.data() <= 1.0);
+        for x in [-1000.0, 1000.0] {
+            let a = Value::new(x);
+            let s = a.sigmoid();
+            s.backward();
+            assert!(!s.data().is_nan());
+            assert_eq!(a.grad(), 0.0);
+        }
+    }
+
+    #[test]
+    fn exp_zero() {
+        let a = Value::new(0.0);
+        let c = a.exp();
+
+        c.backward();
+
+        assert_eq!(c.data(), 1.0);
+        assert_eq!(a.grad(), 1.0);
+        assert_eq!(c.op(), Some("exp".to_string()));
+    }
+
+    #[test]
+    fn exp_chain() {
+        // d(e^(a * b))/da = b * e^(a * b) = 3 * e^1.5
+        let a = Value::new(0.5);
+        let b = Value::new(3.0);
+        (a.clone() * b.clone()).exp().backward();
+
+        assert_approx_eq!(a.grad(), 3.0 * 4.4816890703380645);
+        assert_approx_eq!(b.grad(), 0.5 * 4.4816890703380645);
+    }
+
+    #[test]
+    fn softmax_large_logits() {
+        let logits = vec![Value::new(1000.0), Value::new(1001.0), Value::new(1002.0)];
+        let probs = softmax(&logits, 1.0);
+
+        assert!(probs.iter().all(|p| p.data().is_finite() && p.data() > 0.0));
+        assert_approx_eq!(probs.iter().map(|p| p.data()).sum::<f64>(), 1.0);
+        assert_approx_eq!(probs[2].data(), 1.0 / (1.0 + (-1f64).exp() + (-2f64).exp()));
+
+        probs[2].backward();
+        assert!(logits.iter().all(|l| l.grad().is_finite()));
+        // d(p_i)/d(x_i) = p_i * (1 - p_i)
+        assert_approx_eq!(logits[2].grad(), probs[2].data() * (1.0 - probs[2].data()));
+    }
+
+    #[test]
+    fn fast_tanh() {
+        for i in -1000..=1000 {
+            let x = i as f64 / 100.0;
+            assert_approx_eq!(super::fast_tanh(x), x.tanh(), FAST_TANH_TOLERANCE);
+        }
+        assert_eq!(super::fast_tanh(0.0), 0.0);
+        assert_eq!(super::fast_tanh(100.0), 1.0);
+
+        let a = Value::new(0.7);
+        let b = a.tanh_fast();
+        b.backward();
+        assert_approx_eq!(b.data(), 0.7f64.tanh(), 1e-6);
+        assert_approx_eq!(a.grad(), 1.0 - b.data() * b.data());
+    }
+
+    #[test]
+    fn softmax_temperature() {
+        let logits = vec![Value::new(1.0), Value::new(2.0), Value::new(4.0)];
+        let plain = softmax(&logits, 1.0);
+        let soft = softmax(&logits, 5.0);
+        let sharp = softmax(&logits, 0.5);
+
+        let expected = 1.0 / ((-3f64).exp() + (-2f64).exp() + 1.0);
+        assert_approx_eq!(plain[2].data(), expected);
+        assert!(soft[2].data() < plain[2].data() && soft[0].data() > plain[0].data());
+        assert!(sharp[2].data() > plain[2].data());
+        assert_approx_eq!(soft.iter().map(|p| p.data()).sum::<f64>(), 1.0);
+
+        // the scaling is differentiated: d(p_i)/d(x_i) = p_i * (1 - p_i) / T
+        soft[2].backward();
+        assert_approx_eq!(
+            logits[2].grad(),
+            soft[2].data() * (1.0 - soft[2].data()) / 5.0
+        );
+    }
+
+    #[test]
+    fn clamp() {
+        let a = Value::new(0.5);
+        let b = Value::new(3.0);
+        let c = a.clamp(-1.0, 1.0) + b.clamp(-1.0, 1.0);
+        c.backward();
+        assert_eq!(c.data(), 1.5);
+        assert_eq!(a.grad(), 1.0);
+        assert_eq!(b.grad(), 0.0);
+        assert_eq!(c.compile().call(&[0.5, 3.0]), 1.5);
+    }
+
+    #[test]
+    fn custom_binary() {
+        // a^b for a > 0
+        let pow = |a: &Value, b: &Value| {
+            a.custom_binary(
+                b,
+                "^",
+                f64::powf,
+                |a, b| b * a.powf(b - 1.0),
+                |a, b| a.powf(b) * a.ln(),
+            )
+        };
+        let a = Value::new(2.0);
+        let b = Value::new(3.0);
+        let c = pow(&a, &b);
+        c.backward();
+        assert_eq!(c.data(), 8.0);
+        assert_eq!(c.op().as_deref(), Some("^"));
+        assert_approx_eq!(a.grad(), 12.0);
+        assert_approx_eq!(b.grad(), 8.0 * 2f64.ln());
+
+        // both partials flow into an operand combined with itself
+        let d = Value::new(2.0);
+        let e = pow(&d, &d);
+        e.backward();
+        assert_approx_eq!(d.grad(), 4.0 * (1.0 + 2f64.ln()));
+        assert_approx_eq!(e.compile_backward()(&[2.0])[0], d.grad());
+        assert!(e.to_expr_json().is_err());
+    }
+
+    #[test]
+    fn sqrt() {
+        let a = Value::new(4.0);
+        let c = a.sqrt();
+
+        c.backward();
+
+        assert_eq!(c.data(), 2.0);
+        assert_eq!(a.grad(), 0.25);
+
+        let b = Value::new(0.0);
+        let c = b.sqrt_eps(1e-4);
+        c.backward();
+        assert_approx_eq!(c.data(), 0.01);
+        assert_approx_eq!(b.grad(), 50.0);
+    }
 }
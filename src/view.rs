@@ -3,7 +3,7 @@ use petgraph::dot::RankDir::LR;
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::NodeIndex;
 use petgraph::Graph;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::write;
 use std::process::Command;
@@ -20,34 +20,97 @@ impl NodeData {
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct ViewOptions {
+    top_k: Option<usize>,
+    grad_first: bool,
+    grad_penwidth: bool,
+}
+
+impl ViewOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render only the `k` nodes with the largest |grad| and their immediate neighbors.
+    pub fn with_top_k(mut self, k: usize) -> Self {
+        self.top_k = Some(k);
+        self
+    }
+
+    /// Show `grad` before `data` in every node, to emphasize the backward pass.
+    pub fn with_grad_first(mut self) -> Self {
+        self.grad_first = true;
+        self
+    }
+
+    /// Draw every edge with a `penwidth` from 1 to 5 proportional to the |grad| of the
+    /// node it leaves, so the paths that carry most of the gradient stand out. Needs the
+    /// grads of a prior `backward()`.
+    pub fn with_grad_penwidth(mut self) -> Self {
+        self.grad_penwidth = true;
+        self
+    }
+}
+
 pub fn print_computation_graph(root: &Value, output_path: Option<&str>) -> String {
-    let mut graph = Graph::<NodeData, ()>::new();
-    let (nodes, edges) = root.trace();
+    print_computation_graph_with(root, output_path, &ViewOptions::default())
+}
+
+pub fn print_computation_graph_with(
+    root: &Value,
+    output_path: Option<&str>,
+    options: &ViewOptions,
+) -> String {
+    // every edge carries the |grad| of the node it leaves
+    let mut graph = Graph::<NodeData, f64>::new();
+    let (mut nodes, mut edges) = root.trace();
+    if let Some(k) = options.top_k {
+        let mut by_grad: Vec<_> = nodes.iter().collect();
+        by_grad.sort_by(|a, b| b.grad.abs().total_cmp(&a.grad.abs()));
+        let top: HashSet<_> = by_grad.iter().take(k).map(|n| n.id).collect();
+
+        let mut keep = top.clone();
+        for (n1, n2) in &edges {
+            if top.contains(&n1.id) || top.contains(&n2.id) {
+                keep.insert(n1.id);
+                keep.insert(n2.id);
+            }
+        }
+        nodes.retain(|n| keep.contains(&n.id));
+        edges.retain(|(n1, n2)| keep.contains(&n1.id) && keep.contains(&n2.id));
+    }
     let mut node_map = HashMap::with_capacity(nodes.len());
     for node in &nodes {
-        let _node_id = graph.add_node(NodeData::new(
+        let label = if options.grad_first {
+            format!(
+                "{{ {} | grad {:.4} | data {:.4} }}",
+                node.label, node.grad, node.data
+            )
+        } else {
             format!(
                 "{{ {} | data {:.4} | grad {:.4} }}",
                 node.label, node.data, node.grad
-            ),
-            "record".to_string(),
-        ));
-        node_map.insert(node.uuid.to_string(), _node_id);
+            )
+        };
+        let _node_id = graph.add_node(NodeData::new(label, "record".to_string()));
+        node_map.insert(node.id.to_string(), _node_id);
         if let Some(op) = &node.op {
             let _op_id = graph.add_node(NodeData::new(op.to_string(), "circle".to_string()));
-            graph.add_edge(_op_id, _node_id, ());
-            let mut op_key = node.uuid.to_string();
+            graph.add_edge(_op_id, _node_id, node.grad.abs());
+            let mut op_key = node.id.to_string();
             op_key += op;
             node_map.insert(op_key, _op_id);
         }
     }
     for (n1, n2) in &edges {
-        let n1_key = n1.uuid.to_string();
-        let mut n2_key = n2.uuid.to_string();
+        let n1_key = n1.id.to_string();
+        let mut n2_key = n2.id.to_string();
         let op = if let Some(op) = &n2.op { op } else { "" };
         n2_key += op;
-        graph.add_edge(node_map[&n1_key], node_map[&n2_key], ());
+        graph.add_edge(node_map[&n1_key], node_map[&n2_key], n1.grad.abs());
     }
+    let max_grad = graph.edge_weights().fold(0.0, |m: f64, g| m.max(*g));
 
     let get_node_attrs = |_, node: (NodeIndex, &NodeData)| {
         format!("label=\"{}\" shape={}", node.1.label, node.1.shape)
@@ -58,8 +121,17 @@ pub fn print_computation_graph(root: &Value, output_path: Option<&str>) -> Strin
         Dot::with_attr_getters(
             &graph,
             &[Config::EdgeNoLabel, Config::RankDir(LR)],
-            &|_, _| {
-                String::new() // No extra edge attributes
+            &|_, edge| {
+                if options.grad_penwidth {
+                    let scale = if max_grad > 0.0 {
+                        edge.weight() / max_grad
+                    } else {
+                        0.0
+                    };
+                    format!("penwidth={:.2}", 1.0 + 4.0 * scale)
+                } else {
+                    String::new() // No extra edge attributes
+                }
             },
             &get_node_attrs
         )
@@ -129,6 +201,72 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_print_computation_graph_top_k() {
+        let a = Value::new(2.0).with_label("a");
+        let b = Value::new(-3.0).with_label("b");
+        let c = Value::new(10.0).with_label("c");
+        let e = (a * b).with_label("e"); // 6.0
+        let d = (e + c).with_label("d");
+        let f = Value::new(-2.0).with_label("f");
+        let l = (d * f).with_label("L");
+        l.backward();
+
+        let dot = print_computation_graph_with(&l, None, &ViewOptions::new().with_top_k(3));
+
+        // a, b and f have the largest grads, e and L are their neighbors
+        assert_eq!(dot.matches("shape=record").count(), 5);
+        assert!(dot.contains("label=\"{ a | data 2.0000 | grad 6.0000 }\""));
+        assert!(!dot.contains("{ c |"));
+        assert!(!dot.contains("{ d |"));
+    }
+
+    #[test]
+    fn test_print_computation_graph_grad_first() {
+        let a = Value::new(2.0).with_label("a");
+        let b = Value::new(-3.0).with_label("b");
+        let l = (a * b).with_label("L");
+        l.backward();
+
+        let dot = print_computation_graph_with(&l, None, &ViewOptions::new().with_grad_first());
+        assert!(dot.contains("label=\"{ L | grad 1.0000 | data -6.0000 }\""));
+        assert!(dot.contains("label=\"{ a | grad -3.0000 | data 2.0000 }\""));
+        assert!(dot.contains("label=\"{ b | grad 2.0000 | data -3.0000 }\""));
+        assert!(!dot.contains("| data -6.0000 | grad"));
+    }
+
+    #[test]
+    fn test_print_computation_graph_grad_penwidth() {
+        let a = Value::new(2.0).with_label("a");
+        let b = Value::new(-3.0).with_label("b");
+        let c = Value::new(0.5).with_label("c");
+        let l = ((a * b) + c).with_label("L");
+        l.backward();
+
+        let plain = print_computation_graph(&l, None);
+        assert!(!plain.contains("penwidth"));
+
+        let dot = print_computation_graph_with(&l, None, &ViewOptions::new().with_grad_penwidth());
+        let edges: Vec<&str> = dot.lines().filter(|line| line.contains("->")).collect();
+        assert_eq!(edges.len(), 6);
+        assert!(edges.iter().all(|edge| edge.contains("penwidth=")));
+
+        // a has the largest |grad| (3), so its edge is the only one at full width
+        let a_index = dot
+            .lines()
+            .find(|line| line.contains("{ a |"))
+            .and_then(|line| line.split_whitespace().next())
+            .unwrap();
+        let thickest: Vec<&str> = edges
+            .into_iter()
+            .filter(|edge| edge.contains("penwidth=5.00"))
+            .collect();
+        assert_eq!(thickest.len(), 1);
+        assert!(thickest[0]
+            .trim_start()
+            .starts_with(&format!("{} ->", a_index)));
+    }
+
     #[test]
     fn test_print_computation_graph_after_backward() {
         let a = Value::new(2.0).with_label("a");